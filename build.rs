@@ -1,8 +1,51 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt::Write;
 use std::fs;
 use std::path::Path;
 
+/// The checked-in language catalogue, compiled into `$OUT_DIR/languages.rs`.
+const LANGUAGES_FILE: &str = "data/languages.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Catalogue {
+    syntax: HashMap<String, Syntax>,
+    languages: BTreeMap<String, Entry>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+struct Syntax {
+    line_comments: Option<Vec<String>>,
+    block_comments: Option<Vec<(String, String)>>,
+    nested_comments: Option<bool>,
+    quotes: Option<Vec<(String, String)>>,
+    char_literals: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    category: String,
+    syntax: Option<String>,
+    line_comments: Option<Vec<String>>,
+    block_comments: Option<Vec<(String, String)>>,
+    nested_comments: Option<bool>,
+    quotes: Option<Vec<(String, String)>>,
+    char_literals: Option<bool>,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    filenames: Vec<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
 fn main() {
+    generate_languages();
+
     // Retrieve environment variables set by Cargo
     let target = env::var("TARGET").expect("TARGET environment variable not set");
     let profile = env::var("PROFILE").expect("PROFILE environment variable not set");
@@ -38,3 +81,78 @@ fn main() {
         println!("cargo:warning=Built binary not found at {:?}", built_binary);
     }
 }
+
+/// Turns the catalogue into a `LANGUAGE_LIST` of `Language` values and
+/// `(key, language name)` lists for extensions, file names and aliases, which
+/// src/languages.rs includes and indexes.
+fn generate_languages() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR not set");
+    let text = fs::read_to_string(Path::new(&manifest_dir).join(LANGUAGES_FILE))
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", LANGUAGES_FILE, e));
+    let catalogue: Catalogue =
+        toml::from_str(&text).unwrap_or_else(|e| panic!("Invalid {}: {}", LANGUAGES_FILE, e));
+
+    let mut languages = String::new();
+    let mut extensions = BTreeMap::new();
+    let mut filenames = BTreeMap::new();
+    let mut aliases = BTreeMap::new();
+    for (name, entry) in &catalogue.languages {
+        let preset = match &entry.syntax {
+            Some(syntax) => catalogue
+                .syntax
+                .get(syntax)
+                .cloned()
+                .unwrap_or_else(|| panic!("{}: unknown syntax '{}'", name, syntax)),
+            None => Syntax::default(),
+        };
+        let category = match entry.category.as_str() {
+            "programming" => "Programming",
+            "markup" => "Markup",
+            "data" => "Data",
+            "prose" => "Prose",
+            other => panic!("{}: unknown category '{}'", name, other),
+        };
+        let line_comments = entry.line_comments.as_ref().or(preset.line_comments.as_ref());
+        let block_comments = entry.block_comments.as_ref().or(preset.block_comments.as_ref());
+        let quotes = entry.quotes.as_ref().or(preset.quotes.as_ref());
+        let nested_comments = entry.nested_comments.or(preset.nested_comments).unwrap_or(false);
+        let char_literals = entry.char_literals.or(preset.char_literals).unwrap_or(false);
+        writeln!(
+            languages,
+            "    language({:?}, Category::{}, &{:?}, &{:?}, {}, &{:?}, {}),",
+            name,
+            category,
+            line_comments.cloned().unwrap_or_default(),
+            block_comments.cloned().unwrap_or_default(),
+            nested_comments,
+            quotes.cloned().unwrap_or_default(),
+            char_literals,
+        )
+        .unwrap();
+
+        for (table, keys) in [
+            (&mut extensions, entry.extensions.clone()),
+            (&mut filenames, entry.filenames.iter().map(|f| f.to_lowercase()).collect()),
+            (&mut aliases, entry.aliases.clone()),
+        ] {
+            for key in keys {
+                if let Some(other) = table.insert(key.clone(), name.clone()) {
+                    panic!("'{}' is listed for both {} and {}", key, other, name);
+                }
+            }
+        }
+    }
+
+    let mut generated = format!("static LANGUAGE_LIST: &[Language] = &[\n{}];\n", languages);
+    for (list, table) in [
+        ("EXTENSION_LIST", &extensions),
+        ("FILENAME_LIST", &filenames),
+        ("ALIAS_LIST", &aliases),
+    ] {
+        writeln!(generated, "static {}: &[(&str, &str)] = &{:?};", list, table.iter().collect::<Vec<_>>())
+            .unwrap();
+    }
+    fs::write(Path::new(&out_dir).join("languages.rs"), generated)
+        .expect("Failed to write the generated language table");
+}
@@ -1,24 +1,272 @@
 use std::error::Error;
-use clap::{App, Arg};
-use crate::{get_stats, tui};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use clap::{App, Arg, ArgMatches};
+use encoding_rs::Encoding;
+use crate::config::Config;
+use crate::identity::{GroupBy, Identities};
+use crate::languages::Category;
+use crate::output::{self, OutputFormat, OutputOptions, Table};
+use crate::{get_stats, languages, tui, ScanOptions};
 
 pub fn execute() -> Result<(), Box<dyn Error>> {
     let matches = App::new("RustyLines")
         .version("1.0")
         .author("HakeemsGit")
-        .about("Count lines of code in a directory")
+        .about("Count lines of code in directories and files")
         .arg(
             Arg::with_name("path")
-                .help("The path to analyze")
+                .help("The directories and files to analyze")
                 .required(true)
+                .multiple_values(true)
                 .index(1),
         )
+        .arg(
+            Arg::with_name("no-ignore")
+                .long("no-ignore")
+                .help("Don't respect .gitignore, .ignore, .rustylinesignore or git exclude files"),
+        )
+        .arg(
+            Arg::with_name("include-generated")
+                .long("include-generated")
+                .help("Count generated files and lockfiles"),
+        )
+        .arg(
+            Arg::with_name("include-minified")
+                .long("include-minified")
+                .help("Count minified files"),
+        )
+        .arg(
+            Arg::with_name("include-vendored")
+                .long("include-vendored")
+                .help("Count files in vendor/, third_party/, node_modules/ and similar directories"),
+        )
+        .arg(
+            Arg::with_name("rev")
+                .long("rev")
+                .takes_value(true)
+                .value_name("COMMITISH")
+                .help("Count and blame the files of a commit, branch or tag instead of the working directory"),
+        )
+        .arg(
+            Arg::with_name("ignore-rev")
+                .long("ignore-rev")
+                .takes_value(true)
+                .value_name("REV")
+                .multiple_occurrences(true)
+                .help("Credit the lines changed by REV to their previous author, e.g. a reformatting commit (repeatable)"),
+        )
+        .arg(
+            Arg::with_name("group-by")
+                .long("group-by")
+                .takes_value(true)
+                .value_name("GROUPING")
+                .possible_values(GroupBy::NAMES)
+                .default_value("person")
+                .help("Credit lines to each person, or to each email domain"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Stop at the first unreadable file or git error instead of reporting it"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .short('j')
+                .takes_value(true)
+                .value_name("N")
+                .help("Number of threads to scan with (defaults to the number of cores)"),
+        )
+        .arg(
+            Arg::with_name("encoding")
+                .long("encoding")
+                .takes_value(true)
+                .value_name("ENCODING")
+                .help("Read files without a byte order mark in ENCODING, e.g. latin1 or shift_jis"),
+        )
+        .arg(
+            Arg::with_name("category")
+                .long("category")
+                .takes_value(true)
+                .value_name("CATEGORY")
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .possible_values(Category::NAMES)
+                .help("Only count languages in these categories (comma-separated or repeated)"),
+        )
+        .arg(
+            Arg::with_name("lang")
+                .long("lang")
+                .takes_value(true)
+                .value_name("LANGUAGE")
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("Only count these languages (comma-separated or repeated)"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .takes_value(true)
+                .value_name("GLOB")
+                .multiple_occurrences(true)
+                .help("Only scan paths matching GLOB, relative to the scanned directory (repeatable)"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .takes_value(true)
+                .value_name("GLOB")
+                .multiple_occurrences(true)
+                .help("Don't scan paths matching GLOB, relative to the scanned directory (repeatable)"),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .takes_value(true)
+                .value_name("N")
+                .help("Don't descend more than N directories below the scanned directory"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short('o')
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(OutputFormat::NAMES)
+                .default_value("tui")
+                .help("How to present the results"),
+        )
+        .arg(
+            Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the report to FILE instead of stdout"),
+        )
+        .arg(
+            Arg::with_name("table")
+                .long("table")
+                .takes_value(true)
+                .value_name("TABLE")
+                .possible_values(Table::NAMES)
+                .default_value("languages")
+                .help("Which table to write with --output csv or tsv"),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .takes_value(true)
+                .value_name("N")
+                .help("Only list the N largest rows of each table in --output markdown"),
+        )
         .get_matches();
 
-    let path = matches
-        .value_of("path")
-        .ok_or("Path argument is required")?;
+    let paths: Vec<&str> = matches
+        .values_of("path")
+        .ok_or("Path argument is required")?
+        .collect();
+
+    let threads = match matches.value_of("threads") {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("Invalid thread count '{}'", value))?,
+        None => 0,
+    };
+
+    let max_depth = match matches.value_of("max-depth") {
+        Some(value) => Some(
+            value
+                .parse()
+                .map_err(|_| format!("Invalid depth '{}'", value))?,
+        ),
+        None => None,
+    };
+
+    // The project config is looked up from the first path.
+    let config = Config::load(Path::new(paths[0]))?;
+    languages::configure(&config)?;
+
+    let encoding = match matches.value_of("encoding") {
+        Some(label) => Some(
+            Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| format!("Unknown encoding '{}'", label))?,
+        ),
+        None => None,
+    };
+
+    let options = ScanOptions {
+        no_ignore: matches.is_present("no-ignore"),
+        threads,
+        categories: matches
+            .values_of("category")
+            .into_iter()
+            .flatten()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?,
+        languages: matches
+            .values_of("lang")
+            .into_iter()
+            .flatten()
+            .map(|name| {
+                languages::find_language(name)
+                    .map(|lang| lang.name)
+                    .ok_or_else(|| format!("Unknown language '{}'", name))
+            })
+            .collect::<Result<_, _>>()?,
+        include: values(&matches, "include"),
+        exclude: values(&matches, "exclude"),
+        max_depth,
+        include_generated: matches.is_present("include-generated"),
+        include_minified: matches.is_present("include-minified"),
+        include_vendored: matches.is_present("include-vendored"),
+        strict: matches.is_present("strict"),
+        encoding,
+        rev: matches.value_of("rev").map(str::to_string),
+        ignore_revs: values(&matches, "ignore-rev"),
+        identities: Identities::new(
+            matches.value_of("group-by").unwrap_or("person").parse()?,
+            &config.aliases,
+        ),
+    };
+
+    let format: OutputFormat = matches.value_of("output").unwrap_or("tui").parse()?;
+    let output_options = OutputOptions {
+        table: matches.value_of("table").unwrap_or("languages").parse()?,
+        top: match matches.value_of("top") {
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid row count '{}'", value))?,
+            ),
+            None => None,
+        },
+    };
+
+    if format == OutputFormat::Tui && matches.is_present("out") {
+        return Err("--out requires a non-interactive --output format".into());
+    }
+
+    let stats = get_stats(&paths, &options)?;
+    if format == OutputFormat::Tui {
+        return tui::run(stats);
+    }
+
+    let mut out: Box<dyn Write> = match matches.value_of("out") {
+        Some(file) => Box::new(BufWriter::new(File::create(file)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    output::write(format, &stats, &output_options, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
 
-    let stats = get_stats(path)?;
-    tui::run(stats)
+fn values(matches: &ArgMatches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(str::to_string)
+        .collect()
 }
@@ -0,0 +1,154 @@
+use crate::languages::Category;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the project-level configuration file, looked up in the scanned
+/// directory and its parents.
+pub const PROJECT_CONFIG: &str = ".rustylines.toml";
+
+/// Settings read from the user-level and project-level config files.
+///
+/// ```toml
+/// [languages.Protobuf]
+/// category = "data"
+/// line_comments = ["//"]
+/// block_comments = [["/*", "*/"]]
+/// quotes = [["\"", "\""]]
+/// extensions = ["proto"]
+///
+/// [extensions]
+/// tmpl = "HTML"
+///
+/// [filenames]
+/// BUILD = "Starlark"
+///
+/// [aliases]
+/// "Jane Doe" = ["jdoe@old-employer.com", "Jane D <jane@example.com>", "janed"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// New languages, or replacement syntax for built-in ones, by name.
+    pub languages: HashMap<String, LanguageConfig>,
+    /// Extension to language name, overriding the built-in mapping.
+    pub extensions: HashMap<String, String>,
+    /// Exact file name to language name, overriding the built-in mapping.
+    pub filenames: HashMap<String, String>,
+    /// Contributor to the identities credited to them, on top of what the
+    /// repository's `.mailmap` merges. Each identity is an email, a name or
+    /// both as `Name <email>`, matched case-insensitively.
+    pub aliases: HashMap<String, Vec<String>>,
+}
+
+/// Comment syntax of a configured language. Fields left out of an entry for
+/// a built-in language keep their built-in value.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LanguageConfig {
+    /// `programming`, `markup`, `data` or `prose`; new languages default to
+    /// `programming`.
+    pub category: Option<Category>,
+    pub line_comments: Option<Vec<String>>,
+    pub block_comments: Option<Vec<(String, String)>>,
+    pub nested_comments: Option<bool>,
+    pub quotes: Option<Vec<(String, String)>>,
+    pub char_literals: Option<bool>,
+    pub extensions: Vec<String>,
+    pub filenames: Vec<String>,
+}
+
+impl Config {
+    /// Reads the user-level config, then the nearest `.rustylines.toml` at or
+    /// above `root`, with the project's settings taking precedence.
+    pub fn load(root: &Path) -> Result<Config, Box<dyn Error>> {
+        let mut config = Config::default();
+        let files = [user_config_path(), project_config_path(root)];
+        for path in files.iter().flatten() {
+            if path.is_file() {
+                config.merge(Config::read(path)?);
+            }
+        }
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e).into())
+    }
+
+    fn merge(&mut self, other: Config) {
+        self.languages.extend(other.languages);
+        self.extensions.extend(other.extensions);
+        self.filenames.extend(other.filenames);
+        self.aliases.extend(other.aliases);
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rustylines/config.toml` or the platform equivalent.
+fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rustylines").join("config.toml"))
+}
+
+fn project_config_path(root: &Path) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    root.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_config_takes_precedence() {
+        let user: Config = toml::from_str(
+            r#"
+            [languages.Protobuf]
+            line_comments = ["//"]
+            extensions = ["proto"]
+
+            [extensions]
+            tmpl = "HTML"
+            h = "C Header"
+
+            [aliases]
+            "Jane Doe" = ["jdoe@old.example"]
+            "#,
+        )
+        .unwrap();
+        let project: Config = toml::from_str(
+            r#"
+            [languages.Protobuf]
+            block_comments = [["/*", "*/"]]
+
+            [extensions]
+            h = "C++ Header"
+            "#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.merge(user);
+        config.merge(project);
+
+        assert_eq!(config.extensions["h"], "C++ Header");
+        assert_eq!(config.extensions["tmpl"], "HTML");
+        assert_eq!(config.aliases["Jane Doe"], ["jdoe@old.example"]);
+        // A language's entry is replaced as a whole.
+        let protobuf = &config.languages["Protobuf"];
+        assert_eq!(protobuf.line_comments, None);
+        assert!(protobuf.extensions.is_empty());
+        assert_eq!(protobuf.block_comments.as_deref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let error = toml::from_str::<Config>("[extension]\nh = \"C\"\n").unwrap_err();
+        assert!(error.to_string().contains("unknown field `extension`"), "{}", error);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::languages::Language;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Code,
+    Comment,
+    Blank,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineCounts {
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+}
+
+impl LineCounts {
+    pub fn add(&mut self, kind: LineKind) {
+        match kind {
+            LineKind::Code => self.code += 1,
+            LineKind::Comment => self.comments += 1,
+            LineKind::Blank => self.blanks += 1,
+        }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.code + self.comments + self.blanks
+    }
+}
+
+/// Lexer state carried from one line to the next.
+enum State {
+    Normal,
+    /// Inside a block comment; holds the closing markers of every open level.
+    Comment(Vec<&'static str>),
+    /// Inside a string literal; holds its closing quote.
+    Str(&'static str),
+}
+
+/// Classifies every line of `content` as code, comment or blank.
+///
+/// A line containing any code outside comments counts as code, even when it
+/// also carries a trailing comment. Comment markers inside string literals
+/// are not treated as comments.
+pub fn classify(content: &[u8], language: &Language) -> Vec<LineKind> {
+    let mut state = State::Normal;
+    let mut kinds = Vec::new();
+
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        let started_in_string = matches!(state, State::Str(_));
+        let mut has_code = false;
+        let mut has_comment = false;
+        let mut i = 0;
+
+        while i < line.len() {
+            let rest = &line[i..];
+            match &mut state {
+                State::Str(close) => {
+                    has_code = true;
+                    if rest[0] == b'\\' {
+                        i += 2;
+                    } else if rest.starts_with(close.as_bytes()) {
+                        i += close.len();
+                        state = State::Normal;
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Comment(stack) => {
+                    has_comment |= !rest[0].is_ascii_whitespace();
+                    let close = stack[stack.len() - 1];
+                    if rest.starts_with(close.as_bytes()) {
+                        i += close.len();
+                        stack.pop();
+                        if stack.is_empty() {
+                            state = State::Normal;
+                        }
+                    } else if let Some((open, close)) = language
+                        .block_comments
+                        .iter()
+                        .find(|(open, _)| language.nested_comments && rest.starts_with(open.as_bytes()))
+                    {
+                        i += open.len();
+                        stack.push(close);
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Normal => {
+                    if rest[0].is_ascii_whitespace() {
+                        i += 1;
+                    } else if let Some((open, close)) = language
+                        .block_comments
+                        .iter()
+                        .find(|(open, _)| rest.starts_with(open.as_bytes()))
+                    {
+                        has_comment = true;
+                        i += open.len();
+                        state = State::Comment(vec![close]);
+                    } else if language
+                        .line_comments
+                        .iter()
+                        .any(|marker| rest.starts_with(marker.as_bytes()))
+                    {
+                        has_comment = true;
+                        break;
+                    } else if let Some(len) = char_literal(rest).filter(|_| language.char_literals) {
+                        has_code = true;
+                        i += len;
+                    } else if let Some((open, close)) = language
+                        .quotes
+                        .iter()
+                        .find(|(open, _)| rest.starts_with(open.as_bytes()))
+                    {
+                        has_code = true;
+                        i += open.len();
+                        state = State::Str(close);
+                    } else {
+                        has_code = true;
+                        i += 1;
+                    }
+                }
+            }
+        }
+
+        let kind = if has_code || started_in_string {
+            LineKind::Code
+        } else if has_comment {
+            LineKind::Comment
+        } else {
+            LineKind::Blank
+        };
+        kinds.push(kind);
+    }
+
+    kinds
+}
+
+/// Length of the character literal at the start of `rest`, such as `'"'`,
+/// `'\''` or `'\u{1F600}'`, or `None` if the `'` there is not one, as in a
+/// lifetime like `'a`.
+fn char_literal(rest: &[u8]) -> Option<usize> {
+    match rest {
+        // The longest escape is `\u{10FFFF}`.
+        [b'\'', b'\\', _, tail @ ..] => {
+            tail.iter().take(9).position(|&b| b == b'\'').map(|end| end + 4)
+        }
+        [b'\'', first, ..] if *first != b'\'' => {
+            let width = match first {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            (rest.get(1 + width) == Some(&b'\'')).then_some(width + 2)
+        }
+        _ => None,
+    }
+}
+
+pub fn count(kinds: &[LineKind]) -> LineCounts {
+    let mut counts = LineCounts::default();
+    for &kind in kinds {
+        counts.add(kind);
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::get_language;
+    use LineKind::{Blank, Code, Comment};
+
+    fn kinds(language: &str, content: &str) -> Vec<LineKind> {
+        classify(content.as_bytes(), get_language(language).unwrap())
+    }
+
+    #[test]
+    fn code_comments_and_blanks() {
+        let source = "// header\n\nfn main() {} // trailing\n  \t\n/* block\n   continues */\n";
+        assert_eq!(kinds("Rust", source), [Comment, Blank, Code, Blank, Comment, Comment]);
+        assert_eq!(kinds("Python", "x = 1\n# note\n"), [Code, Comment]);
+    }
+
+    #[test]
+    fn comment_markers_in_strings() {
+        assert_eq!(kinds("Python", "s = \"# not a comment\"\n"), [Code]);
+        assert_eq!(kinds("C", "s = \"a \\\" /* b\";\n// c\n"), [Code, Comment]);
+        // A line inside a multi-line string is code.
+        assert_eq!(kinds("Python", "s = \"\"\"\n# text\n\"\"\"\n"), [Code, Code, Code]);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let source = "/* outer /* inner */\nstill comment */\ncode();\n";
+        assert_eq!(kinds("Rust", source), [Comment, Comment, Code]);
+        assert_eq!(kinds("C", source), [Comment, Code, Code]);
+    }
+
+    #[test]
+    fn rust_char_literals() {
+        let source = "let q = '\"';\n// one\nlet c = '\\'';\n// two\nlet s = '/';\n// three\n";
+        assert_eq!(kinds("Rust", source), [Code, Comment, Code, Comment, Code, Comment]);
+        assert_eq!(kinds("Rust", "let e = '\\u{1F600}'; let u = 'é';\n// c\n"), [Code, Comment]);
+        // Lifetimes are not character literals.
+        let source = "fn f<'a>(x: &'a str) -> &'a str { \"x\" }\n// c\n";
+        assert_eq!(kinds("Rust", source), [Code, Comment]);
+    }
+}
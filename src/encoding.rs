@@ -0,0 +1,116 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+/// How much of a file without a byte order mark is fed to the encoding
+/// detector when it is not valid UTF-8.
+const DETECTION_BYTES: usize = 64 * 1024;
+
+/// A source file ready for counting.
+#[derive(Debug)]
+pub struct Decoded {
+    /// The text without its byte order mark. UTF-8 and single-byte encodings
+    /// are counted as read, as an ASCII byte in them is always that ASCII
+    /// character. Anything else is transcoded to UTF-8 first: UTF-16, and
+    /// legacy multi-byte encodings such as Shift_JIS, whose two-byte
+    /// characters may end in a byte that reads as `\` and would escape a
+    /// closing quote.
+    pub content: Vec<u8>,
+    /// The file as read, when `content` differs from it.
+    pub raw: Option<Vec<u8>>,
+    pub encoding: &'static Encoding,
+    /// Size of the file as read, in bytes.
+    pub size: u64,
+}
+
+/// Works out the encoding of `raw`: a byte order mark decides, then
+/// `forced` if given, then valid UTF-8 (which includes plain ASCII), and
+/// failing that a guess from the byte frequencies of the first 64 KiB.
+pub fn decode(raw: Vec<u8>, forced: Option<&'static Encoding>) -> Decoded {
+    let size = raw.len() as u64;
+    let (encoding, bom_length) = match Encoding::for_bom(&raw) {
+        Some(bom) => bom,
+        None => (forced.unwrap_or_else(|| detect(&raw)), 0),
+    };
+
+    let as_read = encoding == UTF_8 || encoding.is_single_byte();
+    let (content, raw) = if as_read && bom_length == 0 {
+        (raw, None)
+    } else if as_read {
+        (raw[bom_length..].to_vec(), Some(raw))
+    } else {
+        let content = encoding
+            .decode_without_bom_handling(&raw[bom_length..])
+            .0
+            .into_owned()
+            .into_bytes();
+        (content, Some(raw))
+    };
+    Decoded {
+        content,
+        raw,
+        encoding,
+        size,
+    }
+}
+
+impl Decoded {
+    /// The bytes of the file as read, e.g. to compare with a committed blob.
+    pub fn original(&self) -> &[u8] {
+        self.raw.as_deref().unwrap_or(&self.content)
+    }
+}
+
+fn detect(raw: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(raw).is_ok() {
+        return UTF_8;
+    }
+    let sample = &raw[..raw.len().min(DETECTION_BYTES)];
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, sample.len() == raw.len());
+    detector.guess(None, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, UTF_16LE, WINDOWS_1252};
+
+    #[test]
+    fn utf8_is_kept_as_read() {
+        let decoded = decode("let s = \"é\";\n".as_bytes().to_vec(), None);
+        assert_eq!(decoded.encoding, UTF_8);
+        assert_eq!(decoded.content, "let s = \"é\";\n".as_bytes());
+        assert!(decoded.raw.is_none());
+        assert_eq!(decoded.size, 14);
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let decoded = decode(b"\xEF\xBB\xBFx = 1\n".to_vec(), None);
+        assert_eq!(decoded.encoding, UTF_8);
+        assert_eq!(decoded.content, b"x = 1\n");
+        assert_eq!(decoded.original(), b"\xEF\xBB\xBFx = 1\n");
+
+        // The byte order mark wins over a forced encoding.
+        let decoded = decode(b"\xFF\xFEx\0\n\0".to_vec(), Some(WINDOWS_1252));
+        assert_eq!(decoded.encoding, UTF_16LE);
+        assert_eq!(decoded.content, b"x\n");
+        assert_eq!(decoded.size, 6);
+    }
+
+    #[test]
+    fn single_byte_encodings_are_kept_as_read() {
+        let decoded = decode(b"s = \"caf\xE9\"\n".to_vec(), Some(WINDOWS_1252));
+        assert_eq!(decoded.content, b"s = \"caf\xE9\"\n");
+        assert!(decoded.raw.is_none());
+    }
+
+    #[test]
+    fn multi_byte_encodings_are_transcoded() {
+        // The second byte of ソ in Shift_JIS is 0x5C, a backslash in ASCII.
+        let raw = b"s = \"\x83\x5C\"\n# comment\n".to_vec();
+        let decoded = decode(raw.clone(), Some(SHIFT_JIS));
+        assert_eq!(decoded.content, "s = \"ソ\"\n# comment\n".as_bytes());
+        assert_eq!(decoded.original(), raw);
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// Decides which entries the walker yields from the `--include` and
+/// `--exclude` globs. Patterns are matched against paths relative to the
+/// scan root with `/` separators, and `*` does not cross a `/`; use `**` to
+/// match any number of directories. As in `.gitignore`, a pattern without a
+/// `/`, such as `*.rs`, matches at any depth.
+#[derive(Debug)]
+pub struct PathFilter {
+    root: PathBuf,
+    include: GlobSet,
+    exclude: GlobSet,
+    /// Directories matched by an exclude pattern ending in `/**`, which are
+    /// pruned instead of walked and then found empty.
+    exclude_dirs: GlobSet,
+}
+
+impl PathFilter {
+    pub fn new(root: &Path, include: &[String], exclude: &[String]) -> Result<PathFilter, String> {
+        let mut exclude_dirs = Vec::new();
+        for pattern in exclude {
+            if let Some(dir) = pattern.strip_suffix("/**") {
+                exclude_dirs.push(dir.to_string());
+            }
+        }
+        Ok(PathFilter {
+            root: root.to_path_buf(),
+            include: build_set(include)?,
+            exclude: build_set(exclude)?,
+            exclude_dirs: build_set(&exclude_dirs)?,
+        })
+    }
+
+    /// Whether the walker should yield `path` and, for a directory, descend
+    /// into it. Include patterns only apply to files, as a directory may hold
+    /// matching files at any depth.
+    pub fn allows(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+            _ => return true,
+        };
+        if is_dir {
+            return !self.exclude.is_match(relative) && !self.exclude_dirs.is_match(relative);
+        }
+        !self.exclude.is_match(relative)
+            && (self.include.is_empty() || self.include.is_match(relative))
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let unanchored = if pattern.contains('/') {
+            pattern.clone()
+        } else {
+            format!("**/{}", pattern)
+        };
+        let glob = GlobBuilder::new(&unanchored)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid glob '{}': {}", pattern, e.kind()))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Invalid glob set: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect();
+        let (include, exclude): (Vec<String>, Vec<String>) = (strings(include), strings(exclude));
+        PathFilter::new(Path::new("/repo"), &include, &exclude).unwrap()
+    }
+
+    fn allows(filter: &PathFilter, path: &str, is_dir: bool) -> bool {
+        filter.allows(&Path::new("/repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn includes_match_files_at_any_depth() {
+        let filter = path_filter(&["*.rs"], &[]);
+        assert!(allows(&filter, "main.rs", false));
+        assert!(allows(&filter, "src/deep/lib.rs", false));
+        assert!(!allows(&filter, "README.md", false));
+        // Directories are walked whatever the include patterns say.
+        assert!(allows(&filter, "docs", true));
+        assert!(filter.allows(Path::new("/repo"), true));
+    }
+
+    #[test]
+    fn star_does_not_cross_directories() {
+        let filter = path_filter(&["src/*.rs"], &[]);
+        assert!(allows(&filter, "src/main.rs", false));
+        assert!(!allows(&filter, "src/bin/tool.rs", false));
+        let filter = path_filter(&["src/**/*.rs"], &[]);
+        assert!(allows(&filter, "src/bin/tool.rs", false));
+    }
+
+    #[test]
+    fn excludes_win_over_includes() {
+        let filter = path_filter(&["*.rs"], &["*_test.rs", "vendor/**"]);
+        assert!(allows(&filter, "src/lib.rs", false));
+        assert!(!allows(&filter, "src/lib_test.rs", false));
+        assert!(!allows(&filter, "vendor/dep/lib.rs", false));
+        assert!(!allows(&filter, "vendor", true));
+        assert!(allows(&filter, "src", true));
+    }
+
+    #[test]
+    fn invalid_globs() {
+        let error = PathFilter::new(Path::new("/repo"), &["[a".to_string()], &[]).unwrap_err();
+        assert!(error.starts_with("Invalid glob '[a'"), "{}", error);
+    }
+}
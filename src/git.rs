@@ -1,11 +1,27 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use git2::{BlameOptions, Branch, BranchType, Config, Oid, Repository};
+use std::sync::{Arc, Mutex};
+use git2::{
+    BlameOptions, Branch, BranchType, Config, DiffOptions, FileMode, ObjectType, Oid, Patch,
+    Repository, Signature, Tree,
+};
+
+use crate::encoding::{self, Decoded};
+
+/// File listing revisions to leave out of blame, at the top of the working
+/// directory. It is read whether or not `blame.ignoreRevsFile` names it, as
+/// GitHub does.
+const IGNORE_REVS_FILE: &str = ".git-blame-ignore-revs";
 
 #[derive(Debug, thiserror::Error)]
 pub enum GitError {
     #[error("No default branch found")]
     NoBranch,
+    #[error("Cannot ignore revision: {0}")]
+    IgnoreRev(String),
     #[error("Git error: {0}")]
     Git(#[from] git2::Error),
     #[error("IO error: {0}")]
@@ -14,56 +30,403 @@ pub enum GitError {
     Other(String),
 }
 
+impl GitError {
+    /// Whether the error only means that there is no repository to open.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, GitError::Git(e) if e.code() == git2::ErrorCode::NotFound)
+    }
+}
+
 type Result<T> = std::result::Result<T, GitError>;
 
-pub fn get_file_info(path: &Path) -> Result<(usize, HashMap<String, usize>)> {
-    let repo = Repository::discover(path)?;
-    let workdir = repo
-        .workdir()
-        .ok_or_else(|| GitError::Other("Not a git repository".into()))?;
+/// A run of consecutive lines attributed to one author, whose name and email
+/// have been resolved through the repository's `.mailmap`. `start` is the
+/// zero-based index of the first line in the file.
+#[derive(Debug, Clone)]
+pub struct BlameHunk {
+    pub name: String,
+    pub email: String,
+    /// The commit the lines were last changed in, and its author time in
+    /// seconds since the Unix epoch; `None` for lines not committed yet.
+    pub commit: Option<(Oid, i64)>,
+    pub start: usize,
+    pub lines: usize,
+}
+
+/// A file in the tree of the commit being blamed, see
+/// `GitContext::tree_files`.
+#[derive(Debug, Clone)]
+pub struct TreeFile {
+    /// The path as a walk of the working directory would have yielded it.
+    pub path: PathBuf,
+    pub blob: Oid,
+}
+
+/// Blame results shared between every worker scanning the same repository,
+/// keyed by the commit blamed at and the path within the repository.
+type BlameCache = Arc<Mutex<HashMap<(Oid, PathBuf), Vec<BlameHunk>>>>;
+
+/// Repository state resolved once per scan root: the repository handle, the
+/// commit files are blamed at (`HEAD`, or the revision given with `--rev`),
+/// its tree, the revisions blame looks past and the local user identity used
+/// for lines that have not been committed yet.
+pub struct GitContext {
+    repo: Repository,
+    /// The scan root as given on the command line.
+    root: PathBuf,
+    /// The scan root relative to the repository's working directory.
+    root_in_repo: PathBuf,
+    target: Option<Oid>,
+    tree: Option<Oid>,
+    /// Whether files are read from the tree of `target` rather than the
+    /// working directory: with `--rev`, or in a bare repository. The working
+    /// directory's ignore rules then do not apply.
+    pinned: bool,
+    /// Commits whose changes are credited to the previous author of each
+    /// line, such as mass reformatting.
+    ignore_revs: Arc<HashSet<Oid>>,
+    /// Name and email of the local user.
+    current_user: (String, String),
+    blame_cache: BlameCache,
+}
 
-    let relative_path = get_relative_path(path, workdir)?;
+impl GitContext {
+    /// Opens the repository containing `root`. `ignore_revs` are revisions
+    /// to look past in blame, on top of those listed in
+    /// `.git-blame-ignore-revs` and `blame.ignoreRevsFile`.
+    pub fn open(root: &Path, rev: Option<&str>, ignore_revs: &[String]) -> Result<GitContext> {
+        let repo = Repository::discover(root)?;
+        // A bare repository has no working directory to walk; its whole tree
+        // is scanned instead.
+        let root_in_repo = match repo.workdir() {
+            Some(workdir) => get_relative_path(&root.canonicalize()?, &workdir.canonicalize()?)?,
+            None => PathBuf::new(),
+        };
 
-    // Check if the file is ignored or doesn't exist in the repo
-    if repo.is_path_ignored(&relative_path)?
-        || !file_exists_in_repo(&repo, &relative_path, None)?
-    {
+        // An unborn branch has nothing to blame; every file then belongs to
+        // the current user.
+        let target = match rev {
+            Some(rev) => Some(repo.revparse_single(rev)?.peel_to_commit()?.id()),
+            None => get_default_branch(&repo).ok().and_then(|b| b.get().target()),
+        };
+        let tree = match target {
+            Some(oid) => Some(repo.find_commit(oid)?.tree_id()),
+            None => None,
+        };
         let current_user = get_current_user(&repo)?;
-        let lines = std::fs::read_to_string(path)?.lines().count();
-        let mut contributors = HashMap::new();
-        contributors.insert(current_user, lines);
-        return Ok((lines, contributors));
+
+        let pinned = rev.is_some() || repo.is_bare();
+        let ignore_revs = read_ignore_revs(&repo, tree.filter(|_| pinned), ignore_revs)?;
+        Ok(GitContext {
+            repo,
+            root: root.to_path_buf(),
+            root_in_repo,
+            target,
+            tree,
+            pinned,
+            ignore_revs: Arc::new(ignore_revs),
+            current_user,
+            blame_cache: BlameCache::default(),
+        })
+    }
+
+    /// Opens a second handle on the same repository for another thread,
+    /// reusing everything already resolved. `Repository` cannot be shared
+    /// between threads.
+    pub fn try_clone(&self) -> Result<GitContext> {
+        Ok(GitContext {
+            repo: Repository::open(self.repo.path())?,
+            root: self.root.clone(),
+            root_in_repo: self.root_in_repo.clone(),
+            target: self.target,
+            tree: self.tree,
+            pinned: self.pinned,
+            ignore_revs: Arc::clone(&self.ignore_revs),
+            current_user: self.current_user.clone(),
+            blame_cache: Arc::clone(&self.blame_cache),
+        })
     }
 
-    let default_branch = get_default_branch(&repo)?;
-    let blame = blame_file(&repo, &relative_path, default_branch.get().target())?;
+    /// Whether files are to be read with `tree_files` and `read_blob` instead
+    /// of from the working directory.
+    pub fn reads_tree(&self) -> bool {
+        self.pinned
+    }
 
-    Ok(process_blame(blame))
+    /// The commit files are blamed at, or `None` on an unborn branch.
+    pub fn commit_id(&self) -> Option<Oid> {
+        self.target
+    }
+
+    /// Blames the file at `path`, which has `lines` lines. `working` is its
+    /// content on disk, if it was read from there: lines that differ from the
+    /// committed version are credited to the current user.
+    pub fn get_file_info(
+        &self,
+        path: &Path,
+        working: Option<&Decoded>,
+        lines: usize,
+    ) -> Result<Vec<BlameHunk>> {
+        let relative_path = self.relative_path(path)?;
+
+        // Check if the file is ignored or doesn't exist in the repo
+        let target = match self.target {
+            Some(target)
+                if (self.pinned || !self.repo.is_path_ignored(&relative_path)?)
+                    && self.file_exists_in_repo(&relative_path)? =>
+            {
+                target
+            }
+            _ => return Ok(vec![self.uncommitted(0, lines)]),
+        };
+
+        match working {
+            Some(content) => self.blame_working(&relative_path, target, content),
+            None => self.blame(&relative_path, target),
+        }
+    }
+
+    /// `lines` lines from `start` that are not committed yet.
+    fn uncommitted(&self, start: usize, lines: usize) -> BlameHunk {
+        BlameHunk {
+            name: self.current_user.0.clone(),
+            email: self.current_user.1.clone(),
+            commit: None,
+            start,
+            lines,
+        }
+    }
+
+    /// Blames `working`, the content of `path` on disk, on top of `commit`:
+    /// lines unchanged since then keep their blame, and the rest belong to
+    /// the current user. libgit2's `Blame::blame_buffer` is not used, as it
+    /// loses the signatures of the hunks it splits. A transcoded file, such
+    /// as UTF-16, is diffed as decoded text, which libgit2 would otherwise
+    /// take for binary and report no changes in.
+    fn blame_working(
+        &self,
+        path: &Path,
+        commit: Oid,
+        working: &Decoded,
+    ) -> Result<Vec<BlameHunk>> {
+        let committed = self.blame(path, commit)?;
+        let tree = self.repo.find_commit(commit)?.tree()?;
+        let blob = self.repo.find_blob(tree.get_path(path)?.id())?;
+        if blob.content() == working.original() {
+            return Ok(committed);
+        }
+
+        let old = match working.raw {
+            Some(_) => {
+                let decoded = encoding::decode(blob.content().to_vec(), Some(working.encoding));
+                Cow::Owned(decoded.content)
+            }
+            None => Cow::Borrowed(blob.content()),
+        };
+        let working = &working.content;
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let patch = Patch::from_buffers(&old, None, working, None, Some(&mut options))?;
+        let mut hunks = Vec::new();
+        // The first unchanged line on either side, one-based.
+        let (mut old_line, mut new_line) = (1, 1);
+        for [old_start, old_lines, new_start, new_lines] in diff_hunks(&patch)? {
+            let old_first = if old_lines == 0 { old_start + 1 } else { old_start };
+            let new_first = if new_lines == 0 { new_start + 1 } else { new_start };
+            copy_hunks(&committed, old_line - 1, new_line - 1, new_first - new_line, &mut hunks);
+            if new_lines > 0 {
+                push_hunk(&mut hunks, self.uncommitted(new_first - 1, new_lines));
+            }
+            old_line = old_first + old_lines;
+            new_line = new_first + new_lines;
+        }
+        let rest = (count_lines(working) + 1).saturating_sub(new_line);
+        copy_hunks(&committed, old_line - 1, new_line - 1, rest, &mut hunks);
+        Ok(hunks)
+    }
+
+    /// Blames `path`, relative to the repository, at `commit`. Lines last
+    /// changed by an ignored revision are credited to whoever changed them
+    /// before it, as `git blame --ignore-revs-file` does.
+    fn blame(&self, path: &Path, commit: Oid) -> Result<Vec<BlameHunk>> {
+        let key = (commit, path.to_path_buf());
+        if let Some(hunks) = self.blame_cache.lock().unwrap().get(&key) {
+            return Ok(hunks.clone());
+        }
+
+        let blame = blame_file(&self.repo, path, Some(commit))?;
+        let mut hunks = Vec::new();
+        for hunk in blame.iter() {
+            if !self.ignore_revs.contains(&hunk.final_commit_id()) {
+                hunks.push(to_blame_hunk(&hunk));
+                continue;
+            }
+            match self.blame_before(&hunk)? {
+                Some(lines) => {
+                    for line in lines {
+                        push_hunk(&mut hunks, line);
+                    }
+                }
+                None => hunks.push(to_blame_hunk(&hunk)),
+            }
+        }
+        self.blame_cache
+            .lock()
+            .unwrap()
+            .insert(key, hunks.clone());
+        Ok(hunks)
+    }
+
+    /// Credits each line of a hunk from an ignored commit to the author of
+    /// the line it replaced in the commit's first parent, one line at a
+    /// time. `None` if the commit has no parent or the file is new in it.
+    fn blame_before(&self, hunk: &git2::BlameHunk) -> Result<Option<Vec<BlameHunk>>> {
+        let commit = self.repo.find_commit(hunk.final_commit_id())?;
+        let (Some(path), Ok(parent)) = (hunk.path(), commit.parent(0)) else {
+            return Ok(None);
+        };
+        let (Ok(old), Ok(new)) = (parent.tree()?.get_path(path), commit.tree()?.get_path(path))
+        else {
+            return Ok(None);
+        };
+        let old = self.repo.find_blob(old.id())?;
+        let new = self.repo.find_blob(new.id())?;
+        let old_lines = count_lines(old.content());
+        if old_lines == 0 {
+            return Ok(None);
+        }
+
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let patch = Patch::from_blobs(&old, None, &new, None, Some(&mut options))?;
+        let changes = diff_hunks(&patch)?;
+
+        let earlier = self.blame(path, parent.id())?;
+        let ignored = to_blame_hunk(hunk);
+        let lines = (0..hunk.lines_in_hunk())
+            .map(|i| {
+                let line = map_line(&changes, hunk.orig_start_line() + i).clamp(1, old_lines) - 1;
+                let owner = earlier
+                    .iter()
+                    .find(|h| h.start <= line && line < h.start + h.lines)
+                    .unwrap_or(&ignored);
+                BlameHunk {
+                    start: ignored.start + i,
+                    lines: 1,
+                    ..owner.clone()
+                }
+            })
+            .collect();
+        Ok(Some(lines))
+    }
+
+    /// Lists the files at or below `root`, a path inside the scan root, in
+    /// the tree of the blamed commit. `allow` sees every path with whether it
+    /// is a directory; directories it rejects are not entered. Symbolic links
+    /// and submodules are left out, and an empty repository has no files.
+    pub fn tree_files(
+        &self,
+        root: &Path,
+        allow: &mut dyn FnMut(&Path, bool) -> bool,
+    ) -> Result<Vec<TreeFile>> {
+        let Some(tree) = self.tree else {
+            return Ok(Vec::new());
+        };
+        let tree = self.repo.find_tree(tree)?;
+        let in_repo = self.relative_path(root)?;
+        let mut files = Vec::new();
+        if in_repo.as_os_str().is_empty() {
+            self.collect_files(&tree, root, allow, &mut files)?;
+            return Ok(files);
+        }
+        let entry = tree.get_path(&in_repo)?;
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let subtree = self.repo.find_tree(entry.id())?;
+                self.collect_files(&subtree, root, allow, &mut files)?;
+            }
+            Some(ObjectType::Blob) => files.push(TreeFile {
+                path: root.to_path_buf(),
+                blob: entry.id(),
+            }),
+            _ => {}
+        }
+        Ok(files)
+    }
+
+    fn collect_files(
+        &self,
+        tree: &Tree,
+        dir: &Path,
+        allow: &mut dyn FnMut(&Path, bool) -> bool,
+        files: &mut Vec<TreeFile>,
+    ) -> Result<()> {
+        for entry in tree.iter() {
+            // Names that are not valid UTF-8 cannot be blamed by path.
+            let Some(name) = entry.name() else {
+                continue;
+            };
+            let path = dir.join(name);
+            match entry.kind() {
+                Some(ObjectType::Tree) if allow(&path, true) => {
+                    let subtree = self.repo.find_tree(entry.id())?;
+                    self.collect_files(&subtree, &path, allow, files)?;
+                }
+                Some(ObjectType::Blob)
+                    if entry.filemode() != i32::from(FileMode::Link) && allow(&path, false) =>
+                {
+                    files.push(TreeFile {
+                        path,
+                        blob: entry.id(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_blob(&self, blob: Oid) -> Result<Vec<u8>> {
+        Ok(self.repo.find_blob(blob)?.content().to_vec())
+    }
+
+    /// The size of a blob, without inflating it.
+    pub fn blob_size(&self, blob: Oid) -> Result<u64> {
+        let (size, _) = self.repo.odb()?.read_header(blob)?;
+        Ok(size as u64)
+    }
+
+    fn relative_path(&self, path: &Path) -> Result<PathBuf> {
+        let in_root = path
+            .strip_prefix(&self.root)
+            .map_err(|e| GitError::Other(format!("Failed to create relative path: {}", e)))?;
+        if in_root.as_os_str().is_empty() {
+            return Ok(self.root_in_repo.clone());
+        }
+        Ok(self.root_in_repo.join(in_root))
+    }
+
+    fn file_exists_in_repo(&self, path: &Path) -> Result<bool> {
+        match self.tree {
+            Some(oid) => Ok(self.repo.find_tree(oid)?.get_path(path).is_ok()),
+            None => Ok(false),
+        }
+    }
 }
 
 fn get_relative_path(path: &Path, workdir: &Path) -> Result<PathBuf> {
     path.strip_prefix(workdir)
-        .or_else(|_| path.strip_prefix("."))
         .map(PathBuf::from)
         .map_err(|e| GitError::Other(format!("Failed to create relative path: {}", e)))
 }
 
-fn file_exists_in_repo(repo: &Repository, path: &Path, target: Option<Oid>) -> Result<bool> {
-    let tree = if let Some(oid) = target {
-        repo.find_commit(oid)?.tree()?
-    } else {
-        repo.head()?.peel_to_tree()?
-    };
-
-    Ok(tree.get_path(path).is_ok())
-}
-
 fn blame_file<'a>(
     repo: &'a Repository,
     path: &Path,
     target: Option<Oid>,
 ) -> Result<git2::Blame<'a>> {
     let mut opts = BlameOptions::new();
+    opts.use_mailmap(true);
     if let Some(oid) = target {
         opts.newest_commit(oid);
     }
@@ -71,28 +434,178 @@ fn blame_file<'a>(
         .map_err(GitError::from)
 }
 
-fn process_blame(blame: git2::Blame) -> (usize, HashMap<String, usize>) {
-    blame
-        .iter()
-        .fold((0, HashMap::new()), |(lines, mut contributors), hunk| {
-            let new_lines = lines + hunk.lines_in_hunk();
-            let name = hunk
-                .final_signature()
-                .name()
-                .unwrap_or("Unknown")
-                .to_string();
-            *contributors.entry(name).or_insert(0) += hunk.lines_in_hunk();
-            (new_lines, contributors)
+fn to_blame_hunk(hunk: &git2::BlameHunk) -> BlameHunk {
+    let signature = hunk.final_signature();
+    BlameHunk {
+        name: signature.name().unwrap_or("Unknown").to_string(),
+        email: signature.email().unwrap_or_default().to_string(),
+        commit: Some((hunk.final_commit_id(), signature.when().seconds())),
+        start: hunk.final_start_line().saturating_sub(1),
+        lines: hunk.lines_in_hunk(),
+    }
+}
+
+/// Appends `hunk` to `hunks`, extending the last one instead if it ends
+/// right before `hunk` and has the same author and commit.
+fn push_hunk(hunks: &mut Vec<BlameHunk>, hunk: BlameHunk) {
+    match hunks.last_mut() {
+        Some(last)
+            if last.start + last.lines == hunk.start
+                && last.commit == hunk.commit
+                && last.name == hunk.name
+                && last.email == hunk.email =>
+        {
+            last.lines += hunk.lines;
+        }
+        _ => hunks.push(hunk),
+    }
+}
+
+/// Appends the blame of `count` lines of `committed` from line `from` to
+/// `hunks`, moved to start at line `to`. Lines are zero-based.
+fn copy_hunks(
+    committed: &[BlameHunk],
+    from: usize,
+    to: usize,
+    count: usize,
+    hunks: &mut Vec<BlameHunk>,
+) {
+    for hunk in committed {
+        let start = hunk.start.max(from);
+        let end = (hunk.start + hunk.lines).min(from + count);
+        if start < end {
+            push_hunk(hunks, BlameHunk {
+                start: start - from + to,
+                lines: end - start,
+                ..hunk.clone()
+            });
+        }
+    }
+}
+
+/// The `[old_start, old_lines, new_start, new_lines]` of every hunk of
+/// `patch`.
+fn diff_hunks(patch: &Patch) -> Result<Vec<[usize; 4]>> {
+    (0..patch.num_hunks())
+        .map(|i| {
+            let (hunk, _) = patch.hunk(i)?;
+            Ok([hunk.old_start(), hunk.old_lines(), hunk.new_start(), hunk.new_lines()]
+                .map(|n| n as usize))
         })
+        .collect()
+}
+
+/// The one-based line on the old side of a diff that `line` on the new side
+/// stands for. `changes` holds the `[old_start, old_lines, new_start,
+/// new_lines]` of each hunk of a diff without context lines, where an empty
+/// side starts at the line before it. A changed line stands for the line at
+/// the same offset in the old side of its hunk, or the last one if the hunk
+/// shrank; an added line for the line before it.
+fn map_line(changes: &[[usize; 4]], line: usize) -> usize {
+    let mut old_line = line;
+    for &[old_start, old_lines, new_start, new_lines] in changes {
+        let old_first = if old_lines == 0 { old_start + 1 } else { old_start };
+        let new_first = if new_lines == 0 { new_start + 1 } else { new_start };
+        if line < new_first {
+            break;
+        }
+        if line < new_first + new_lines {
+            return match old_lines {
+                0 => old_start,
+                _ => old_first + (line - new_first).min(old_lines - 1),
+            };
+        }
+        old_line = line - (new_first + new_lines) + (old_first + old_lines);
+    }
+    old_line
+}
+
+fn count_lines(content: &[u8]) -> usize {
+    let breaks = content.iter().filter(|&&b| b == b'\n').count();
+    match content.last() {
+        Some(b'\n') | None => breaks,
+        Some(_) => breaks + 1,
+    }
+}
+
+/// Collects the commits to look past in blame: `revs` from the command line,
+/// then those listed in `.git-blame-ignore-revs` and every file named by
+/// `blame.ignoreRevsFile`. Relative file names are read from `tree` when
+/// given, and from the working directory otherwise.
+fn read_ignore_revs(repo: &Repository, tree: Option<Oid>, revs: &[String]) -> Result<HashSet<Oid>> {
+    let mut ignored = HashSet::new();
+    for rev in revs {
+        let commit = repo
+            .revparse_single(rev)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| GitError::IgnoreRev(format!("'{}': {}", rev, e.message())))?;
+        ignored.insert(commit.id());
+    }
+
+    // As in git, an empty `blame.ignoreRevsFile` drops the files named
+    // before it.
+    let mut files = vec![PathBuf::from(IGNORE_REVS_FILE)];
+    let config = repo.config()?;
+    let mut entries = config.multivar("blame.ignoreRevsFile", None)?;
+    while let Some(entry) = entries.next() {
+        match entry?.value() {
+            Some("") => files.truncate(1),
+            Some(file) if !files.iter().any(|f| f == Path::new(file)) => {
+                files.push(PathBuf::from(file))
+            }
+            _ => {}
+        }
+    }
+
+    for file in files {
+        let Some(text) = read_repo_file(repo, tree, &file)? else {
+            continue;
+        };
+        for line in text.lines() {
+            let name = line.split('#').next().unwrap_or_default().trim();
+            if name.is_empty() {
+                continue;
+            }
+            match Oid::from_str(name) {
+                Ok(oid) if name.len() == 40 => ignored.insert(oid),
+                _ => {
+                    return Err(GitError::IgnoreRev(format!(
+                        "invalid object name '{}' in {}",
+                        name,
+                        file.display()
+                    )))
+                }
+            };
+        }
+    }
+    Ok(ignored)
 }
 
-fn get_default_branch(repo: &Repository) -> Result<Branch> {
+/// Reads a file named relative to the top of the repository from `tree`, or
+/// from the working directory when there is no tree; `None` if it does not
+/// exist.
+fn read_repo_file(repo: &Repository, tree: Option<Oid>, file: &Path) -> Result<Option<String>> {
+    let bytes = match (tree, repo.workdir()) {
+        (Some(tree), _) if file.is_relative() => match repo.find_tree(tree)?.get_path(file) {
+            Ok(entry) => repo.find_blob(entry.id())?.content().to_vec(),
+            Err(_) => return Ok(None),
+        },
+        (_, workdir) => match fs::read(workdir.unwrap_or(repo.path()).join(file)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        },
+    };
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn get_default_branch(repo: &Repository) -> Result<Branch<'_>> {
     repo.head()
         .map(Branch::wrap)
         .or_else(|_| find_head_branch(repo))
 }
 
-fn find_head_branch(repo: &Repository) -> Result<Branch> {
+fn find_head_branch(repo: &Repository) -> Result<Branch<'_>> {
     repo.branches(Some(BranchType::Local))?
         .filter_map(|b| b.ok())
         .find(|(branch, _)| branch.is_head())
@@ -100,15 +613,77 @@ fn find_head_branch(repo: &Repository) -> Result<Branch> {
         .ok_or(GitError::NoBranch)
 }
 
-fn get_current_user(repo: &Repository) -> Result<String> {
+/// The local user's name and email, as the repository's `.mailmap` maps them.
+fn get_current_user(repo: &Repository) -> Result<(String, String)> {
     let config = repo.config()?;
-    let name = config
-        .get_string("user.name")
-        .or_else(|_| {
-            Config::open_default().and_then(|config| config.get_string("user.name"))
-        })
+    let lookup = |key: &str| {
+        config
+            .get_string(key)
+            .or_else(|_| Config::open_default().and_then(|config| config.get_string(key)))
+    };
+    let name = lookup("user.name")
         .or_else(|_| std::env::var("USER").or_else(|_| std::env::var("USERNAME")))
         .unwrap_or_else(|_| "Unknown".to_string());
+    let email = lookup("user.email").unwrap_or_default();
+
+    let resolved = Signature::now(&name, &email).and_then(|signature| {
+        let signature = repo.mailmap()?.resolve_signature(&signature)?;
+        Ok((
+            signature.name().unwrap_or(&name).to_string(),
+            signature.email().unwrap_or(&email).to_string(),
+        ))
+    });
+    Ok(resolved.unwrap_or((name, email)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_outside_changes_keep_their_offset() {
+        // `a b c d e` to `a X c d e f`.
+        let changes = [[2, 1, 2, 1], [5, 0, 6, 1]];
+        assert_eq!(map_line(&changes, 1), 1);
+        assert_eq!(map_line(&changes, 3), 3);
+        assert_eq!(map_line(&changes, 5), 5);
+        // `a b c d` to `a d`.
+        assert_eq!(map_line(&[[2, 2, 1, 0]], 1), 1);
+        assert_eq!(map_line(&[[2, 2, 1, 0]], 2), 4);
+        assert_eq!(map_line(&[], 7), 7);
+    }
 
-    Ok(name)
+    #[test]
+    fn changed_lines() {
+        assert_eq!(map_line(&[[2, 1, 2, 1]], 2), 2);
+        // `a b c d e` to `a X e`: the hunk shrank.
+        let changes = [[2, 3, 2, 1]];
+        assert_eq!(map_line(&changes, 2), 2);
+        assert_eq!(map_line(&changes, 3), 5);
+        // `a b c` to `a X Y Z c`: the hunk grew.
+        let changes = [[2, 1, 2, 3]];
+        assert_eq!(map_line(&changes, 2), 2);
+        assert_eq!(map_line(&changes, 4), 2);
+        assert_eq!(map_line(&changes, 5), 3);
+    }
+
+    #[test]
+    fn added_lines_stand_for_the_line_before() {
+        // `a b c d e` to `a b c d e f`.
+        assert_eq!(map_line(&[[5, 0, 6, 1]], 6), 5);
+        // `a b` to `a X Y b`.
+        let changes = [[1, 0, 2, 2]];
+        assert_eq!(map_line(&changes, 3), 1);
+        assert_eq!(map_line(&changes, 4), 2);
+        // `a` to `X a`: nothing comes before, which callers clamp.
+        assert_eq!(map_line(&[[0, 0, 1, 1]], 1), 0);
+        assert_eq!(map_line(&[[0, 0, 1, 1]], 2), 1);
+    }
+
+    #[test]
+    fn counting_lines() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"a\nb\n"), 2);
+        assert_eq!(count_lines(b"a\nb"), 2);
+    }
 }
@@ -0,0 +1,335 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// How much of a file is searched for keywords. Anything that settles the
+/// question is nearly always near the top.
+const HEURISTIC_BYTES: usize = 64 * 1024;
+
+/// The language picked for a file with an ambiguous extension, and why.
+#[derive(Debug, Clone, Copy)]
+pub struct Heuristic {
+    pub language: &'static str,
+    pub reason: &'static str,
+}
+
+/// Lower-cased extensions of the files in each directory, for the file
+/// next to an ambiguous one. One index serves a single scan root and is
+/// shared between its threads, so a directory is only listed once.
+#[derive(Debug)]
+pub struct Siblings {
+    /// Whether directories missing from `extensions` are listed on disk;
+    /// with a commit's tree, every directory is listed up front.
+    from_disk: bool,
+    extensions: Mutex<HashMap<PathBuf, Arc<HashSet<String>>>>,
+}
+
+impl Siblings {
+    /// Lists directories in the working directory as they are needed.
+    pub fn disk() -> Siblings {
+        Siblings {
+            from_disk: true,
+            extensions: Mutex::default(),
+        }
+    }
+
+    /// Takes the files next to each other from `files`, every file in the
+    /// directories of a commit's tree being scanned, instead of from the
+    /// working directory, which may hold other files or not exist at all.
+    pub fn tree<'a>(files: impl Iterator<Item = &'a Path>) -> Siblings {
+        let mut listed: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+        for path in files {
+            let extensions = listed.entry(parent(path).to_path_buf()).or_default();
+            if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+                extensions.insert(extension.to_lowercase());
+            }
+        }
+        let extensions = listed
+            .into_iter()
+            .map(|(dir, extensions)| (dir, Arc::new(extensions)))
+            .collect();
+        Siblings {
+            from_disk: false,
+            extensions: Mutex::new(extensions),
+        }
+    }
+
+    /// Extensions of the files next to `path`, lower-cased.
+    fn of(&self, path: &Path) -> Arc<HashSet<String>> {
+        let dir = parent(path);
+        if let Some(extensions) = self.extensions.lock().unwrap().get(dir) {
+            return Arc::clone(extensions);
+        }
+        if !self.from_disk {
+            return Arc::default();
+        }
+
+        let listed = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let extensions: HashSet<String> = fs::read_dir(listed)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let extension = Path::new(&name).extension()?.to_str()?.to_lowercase();
+                Some(extension)
+            })
+            .collect();
+        let extensions = Arc::new(extensions);
+        self.extensions
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), Arc::clone(&extensions));
+        extensions
+    }
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Picks a language for a file whose extension is shared by several
+/// languages, from keywords in `content` and the other files in its
+/// directory. Returns `None` for unambiguous extensions, and when nothing
+/// points either way, so the extension's default applies.
+pub fn disambiguate(path: &Path, content: &[u8], siblings: &Siblings) -> Option<Heuristic> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let content = &content[..content.len().min(HEURISTIC_BYTES)];
+    let text = String::from_utf8_lossy(content);
+    match extension.as_str() {
+        "h" => header(path, &text, siblings),
+        "m" => objective_c_or_matlab(path, &text, siblings),
+        "pl" => perl_or_prolog(&text),
+        "r" => r_or_rebol(&text),
+        "ts" => typescript_or_qt(&text),
+        "v" => verilog_or_coq(&text),
+        _ => None,
+    }
+}
+
+const OBJECTIVE_C_DIRECTIVES: &[&str] = &[
+    "@interface",
+    "@implementation",
+    "@protocol",
+    "@property",
+    "@end",
+    "#import",
+];
+
+fn header(path: &Path, text: &str, siblings: &Siblings) -> Option<Heuristic> {
+    if any_line_starts_with(text, OBJECTIVE_C_DIRECTIVES) {
+        return found("Objective-C", "Objective-C directives");
+    }
+    let cpp_keywords = [
+        "class ",
+        "namespace ",
+        "template",
+        "using namespace",
+        "public:",
+        "protected:",
+        "private:",
+    ];
+    if any_line_starts_with(text, &cpp_keywords) || text.contains("std::") {
+        return found("C++ Header", "C++ keywords");
+    }
+
+    let siblings = siblings.of(path);
+    let has = |extensions: &[&str]| extensions.iter().any(|ext| siblings.contains(*ext));
+    if has(&["m", "mm"]) {
+        found("Objective-C", "Objective-C sources alongside")
+    } else if has(&["cpp", "cc", "cxx", "c++", "hpp", "hh", "hxx"]) {
+        found("C++ Header", "C++ sources alongside")
+    } else if has(&["c"]) {
+        found("C Header", "C sources alongside")
+    } else {
+        None
+    }
+}
+
+fn objective_c_or_matlab(path: &Path, text: &str, siblings: &Siblings) -> Option<Heuristic> {
+    if any_line_starts_with(text, OBJECTIVE_C_DIRECTIVES)
+        || any_line_starts_with(text, &["#include", "#define"])
+    {
+        return found("Objective-C", "Objective-C directives");
+    }
+    if any_line_starts_with(text, &["function ", "classdef ", "%"])
+        || text.lines().any(|line| line.trim() == "end")
+    {
+        return found("MATLAB", "MATLAB keywords");
+    }
+    if siblings.of(path).contains("h") {
+        return found("Objective-C", "headers alongside");
+    }
+    None
+}
+
+fn perl_or_prolog(text: &str) -> Option<Heuristic> {
+    let is_clause = |line: &str| {
+        let line = line.trim_end();
+        line.starts_with(":-") || (!line.starts_with('%') && line.ends_with(":-"))
+    };
+    if text.lines().any(is_clause) || text.contains(") :-") {
+        return found("Prolog", "Prolog clauses");
+    }
+    if any_line_starts_with(
+        text,
+        &["use strict", "use warnings", "my ", "sub ", "package "],
+    ) {
+        return found("Perl", "Perl keywords");
+    }
+    None
+}
+
+fn r_or_rebol(text: &str) -> Option<Heuristic> {
+    if text
+        .lines()
+        .any(|line| line.trim_start().to_uppercase().starts_with("REBOL ["))
+    {
+        return found("Rebol", "Rebol header");
+    }
+    if text.contains("<-") || text.contains("library(") {
+        return found("R", "R keywords");
+    }
+    None
+}
+
+fn typescript_or_qt(text: &str) -> Option<Heuristic> {
+    let start = text.trim_start();
+    if start.starts_with("<?xml") || start.starts_with("<!DOCTYPE TS") || start.starts_with("<TS") {
+        return found("Qt Translation", "Qt Linguist XML");
+    }
+    None
+}
+
+fn verilog_or_coq(text: &str) -> Option<Heuristic> {
+    let vernacular = [
+        "Require ",
+        "Theorem ",
+        "Lemma ",
+        "Proof.",
+        "Qed.",
+        "Definition ",
+        "Fixpoint ",
+        "Inductive ",
+    ];
+    if any_line_starts_with(text, &vernacular) {
+        return found("Coq", "Coq vernacular");
+    }
+    let verilog_keywords = [
+        "module ",
+        "endmodule",
+        "always ",
+        "always@",
+        "assign ",
+        "`timescale",
+    ];
+    if any_line_starts_with(text, &verilog_keywords) {
+        return found("Verilog", "Verilog keywords");
+    }
+    None
+}
+
+fn found(language: &'static str, reason: &'static str) -> Option<Heuristic> {
+    Some(Heuristic { language, reason })
+}
+
+fn any_line_starts_with(text: &str, prefixes: &[&str]) -> bool {
+    text.lines().any(|line| {
+        let line = line.trim_start();
+        prefixes.iter().any(|prefix| line.starts_with(prefix))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(path: &str, content: &str) -> Option<&'static str> {
+        language_with(&Siblings::disk(), path, content)
+    }
+
+    fn language_with(siblings: &Siblings, path: &str, content: &str) -> Option<&'static str> {
+        disambiguate(Path::new(path), content.as_bytes(), siblings).map(|h| h.language)
+    }
+
+    #[test]
+    fn h_files() {
+        let path = "no/such/dir/x.h";
+        assert_eq!(language(path, "@interface Foo : NSObject\n@end\n"), Some("Objective-C"));
+        assert_eq!(language(path, "namespace app {\nclass Foo;\n}\n"), Some("C++ Header"));
+        assert_eq!(language(path, "void f(std::string s);\n"), Some("C++ Header"));
+        assert_eq!(language(path, "int f(void);\n"), None);
+    }
+
+    #[test]
+    fn m_files() {
+        let path = "no/such/dir/x.m";
+        assert_eq!(language(path, "#import <Foundation/Foundation.h>\n"), Some("Objective-C"));
+        assert_eq!(language(path, "function y = f(x)\n  y = x;\nend\n"), Some("MATLAB"));
+        assert_eq!(language(path, "% comment\nx = 1;\n"), Some("MATLAB"));
+        assert_eq!(language(path, "x = 1;\n"), None);
+    }
+
+    #[test]
+    fn pl_files() {
+        assert_eq!(language("x.pl", "parent(X, Y) :- father(X, Y).\n"), Some("Prolog"));
+        assert_eq!(language("x.pl", ":- module(m, []).\n"), Some("Prolog"));
+        assert_eq!(language("x.pl", "use strict;\nmy $x = 1;\n"), Some("Perl"));
+        assert_eq!(language("x.pl", "print 1;\n"), None);
+    }
+
+    #[test]
+    fn other_extensions() {
+        assert_eq!(language("x.R", "REBOL [Title: \"x\"]\n"), Some("Rebol"));
+        assert_eq!(language("x.r", "x <- c(1, 2)\n"), Some("R"));
+        assert_eq!(language("x.ts", "<?xml version=\"1.0\"?>\n<TS>\n"), Some("Qt Translation"));
+        assert_eq!(language("x.ts", "export const x = 1;\n"), None);
+        assert_eq!(language("x.v", "Theorem t : True.\nProof.\nQed.\n"), Some("Coq"));
+        assert_eq!(language("x.v", "module top;\nendmodule\n"), Some("Verilog"));
+    }
+
+    #[test]
+    fn disk_siblings() {
+        let dir = std::env::temp_dir().join(format!("rustylines-siblings-{}", std::process::id()));
+        for (sub, files) in [("objc", ["x.h", "x.M"]), ("c", ["y.h", "y.c"])] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+            for file in files {
+                fs::write(dir.join(sub).join(file), "").unwrap();
+            }
+        }
+        let siblings = Siblings::disk();
+        let path = |file: &str| dir.join(file).display().to_string();
+        let objc = language_with(&siblings, &path("objc/x.h"), "int f(void);\n");
+        let c = language_with(&siblings, &path("c/y.h"), "int f(void);\n");
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(objc, Some("Objective-C"));
+        assert_eq!(c, Some("C Header"));
+    }
+
+    #[test]
+    fn tree_siblings() {
+        let files = ["tree/objc/x.h", "tree/objc/x.m", "tree/c/y.h", "tree/c/y.c"];
+        let siblings = Siblings::tree(files.iter().map(Path::new));
+        let header = |path| language_with(&siblings, path, "int f(void);\n");
+        assert_eq!(header("tree/objc/x.h"), Some("Objective-C"));
+        assert_eq!(header("tree/c/y.h"), Some("C Header"));
+        // Directories outside the tree are not listed on disk.
+        let dir = std::env::temp_dir().join(format!("rustylines-tree-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("z.c"), "").unwrap();
+        let outside = header(&dir.join("z.h").display().to_string());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(outside, None);
+    }
+
+    #[test]
+    fn unambiguous_extensions() {
+        assert_eq!(language("x.rs", "@interface\n"), None);
+        assert_eq!(language("Makefile", "my $x;\n"), None);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Contributor for lines that could not be blamed.
+pub const UNKNOWN: &str = "Unknown";
+
+/// What blamed lines are credited to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    /// One contributor per mailmap-resolved name and email.
+    #[default]
+    Person,
+    /// One contributor per email domain, e.g. `example.com`.
+    Domain,
+}
+
+impl GroupBy {
+    pub const NAMES: &'static [&'static str] = &["person", "domain"];
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "person" => Ok(GroupBy::Person),
+            "domain" => Ok(GroupBy::Domain),
+            _ => Err(format!("Unknown grouping '{}'", s)),
+        }
+    }
+}
+
+/// Turns the author of a blamed line, already resolved through `.mailmap`,
+/// into the contributor it is credited to.
+#[derive(Debug, Clone, Default)]
+pub struct Identities {
+    group_by: GroupBy,
+    /// Lower-cased `name <email>`, email or name to the contributor it is an
+    /// alias of, from the `[aliases]` config section.
+    aliases: HashMap<String, String>,
+}
+
+impl Identities {
+    /// `aliases` maps each contributor to the identities merged into it,
+    /// each written as `Name <email>`, an email or a name.
+    pub fn new(group_by: GroupBy, aliases: &HashMap<String, Vec<String>>) -> Identities {
+        let aliases = aliases
+            .iter()
+            .flat_map(|(contributor, identities)| {
+                identities
+                    .iter()
+                    .map(move |identity| (identity.trim().to_lowercase(), contributor.clone()))
+            })
+            .collect();
+        Identities { group_by, aliases }
+    }
+
+    /// The contributor key for an author. Aliases take precedence over the
+    /// grouping. People are keyed as `Name <email>`, and two people who
+    /// share a name stay apart; see `display_names` for the names shown.
+    pub fn contributor(&self, name: &str, email: &str) -> String {
+        let name = name.trim();
+        let email = email.trim().to_lowercase();
+        let lower_name = name.to_lowercase();
+        let alias = self
+            .aliases
+            .get(&format!("{} <{}>", lower_name, email))
+            .or_else(|| self.aliases.get(&email).filter(|_| !email.is_empty()))
+            .or_else(|| self.aliases.get(&lower_name));
+        if let Some(contributor) = alias {
+            return contributor.clone();
+        }
+
+        match self.group_by {
+            GroupBy::Person if email.is_empty() => name.to_string(),
+            GroupBy::Person => format!("{} <{}>", name, email),
+            GroupBy::Domain => match email.rsplit_once('@') {
+                Some((_, domain)) if !domain.is_empty() => domain.to_string(),
+                _ => UNKNOWN.to_string(),
+            },
+        }
+    }
+}
+
+/// The name to show for each contributor key of the form `Name <email>`:
+/// just the name, unless another contributor goes by the same name.
+pub fn display_names<'a>(keys: impl Iterator<Item = &'a str>) -> HashMap<String, String> {
+    let keys: Vec<&str> = keys.collect();
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for key in &keys {
+        *names.entry(split(key).0).or_default() += 1;
+    }
+    keys.into_iter()
+        .filter_map(|key| {
+            let (name, email) = split(key);
+            (email.is_some() && names[name] == 1).then(|| (key.to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+/// Splits `Name <email>` into its parts; other keys are all name.
+pub fn split(key: &str) -> (&str, Option<&str>) {
+    match key.strip_suffix('>').and_then(|rest| rest.rsplit_once(" <")) {
+        Some((name, email)) => (name, Some(email)),
+        None => (key, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliased(group_by: GroupBy) -> Identities {
+        let mut aliases = HashMap::new();
+        aliases.insert(
+            "Jane Doe".to_string(),
+            vec!["jdoe@old.example".to_string(), " Jane D <JANE@example.com> ".to_string()],
+        );
+        aliases.insert("Bot".to_string(), vec!["ci-bot".to_string()]);
+        Identities::new(group_by, &aliases)
+    }
+
+    #[test]
+    fn people() {
+        let identities = Identities::default();
+        assert_eq!(identities.contributor("Jane", "Jane@Example.com"), "Jane <jane@example.com>");
+        assert_eq!(identities.contributor(" Jane ", ""), "Jane");
+    }
+
+    #[test]
+    fn domains() {
+        let identities = Identities::new(GroupBy::Domain, &HashMap::new());
+        assert_eq!(identities.contributor("Jane", "jane@Example.com"), "example.com");
+        assert_eq!(identities.contributor("Jane", "jane"), UNKNOWN);
+        assert_eq!(identities.contributor("Jane", ""), UNKNOWN);
+    }
+
+    #[test]
+    fn aliases() {
+        for group_by in [GroupBy::Person, GroupBy::Domain] {
+            let identities = aliased(group_by);
+            assert_eq!(identities.contributor("J. Doe", "JDOE@old.example"), "Jane Doe");
+            assert_eq!(identities.contributor("jane d", "jane@example.com"), "Jane Doe");
+            assert_eq!(identities.contributor("CI-Bot", "bot@ci.example"), "Bot");
+        }
+        // The name alone does not match an alias written with an email.
+        let identities = aliased(GroupBy::Person);
+        assert_eq!(identities.contributor("Jane D", "jd@example.org"), "Jane D <jd@example.org>");
+    }
+
+    #[test]
+    fn grouping_names() {
+        assert_eq!("domain".parse(), Ok(GroupBy::Domain));
+        assert_eq!("team".parse::<GroupBy>(), Err("Unknown grouping 'team'".to_string()));
+    }
+
+    #[test]
+    fn display_names_drop_unique_emails() {
+        let keys = [
+            "Jane <jane@example.com>",
+            "Sam <sam@example.com>",
+            "Sam <sam@example.org>",
+            "Alex",
+            "Alex <alex@example.com>",
+            "example.com",
+        ];
+        let names = display_names(keys.into_iter());
+        assert_eq!(names.len(), 1);
+        assert_eq!(names["Jane <jane@example.com>"], "Jane");
+    }
+
+    #[test]
+    fn splitting_keys() {
+        assert_eq!(split("Jane <jane@example.com>"), ("Jane", Some("jane@example.com")));
+        assert_eq!(split("Jane"), ("Jane", None));
+        assert_eq!(split("<odd>"), ("<odd>", None));
+    }
+}
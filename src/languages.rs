@@ -1,47 +1,597 @@
+use crate::config::{Config, LanguageConfig};
+use crate::heuristics::{self, Siblings};
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Comment and string syntax used to split a file into code, comment and
+/// blank lines.
+#[derive(Debug)]
+pub struct Language {
+    pub name: &'static str,
+    pub category: Category,
+    pub line_comments: &'static [&'static str],
+    pub block_comments: &'static [(&'static str, &'static str)],
+    pub nested_comments: bool,
+    pub quotes: &'static [(&'static str, &'static str)],
+    /// Whether `'` opens a character literal such as `'"'` or `'\n'` where
+    /// one fits, and is code otherwise, as in Rust lifetimes.
+    pub char_literals: bool,
+}
+
+/// What kind of content a language holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    #[default]
+    Programming,
+    Markup,
+    Data,
+    Prose,
+}
+
+impl Category {
+    pub const NAMES: &'static [&'static str] = &["programming", "markup", "data", "prose"];
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "programming" => Ok(Category::Programming),
+            "markup" => Ok(Category::Markup),
+            "data" => Ok(Category::Data),
+            "prose" => Ok(Category::Prose),
+            _ => Err(format!("Unknown category '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Category::Programming => "Programming",
+            Category::Markup => "Markup",
+            Category::Data => "Data",
+            Category::Prose => "Prose",
+        };
+        f.write_str(name)
+    }
+}
+
+const fn language(
+    name: &'static str,
+    category: Category,
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    nested_comments: bool,
+    quotes: &'static [(&'static str, &'static str)],
+    char_literals: bool,
+) -> Language {
+    Language {
+        name,
+        category,
+        line_comments,
+        block_comments,
+        nested_comments,
+        quotes,
+        char_literals,
+    }
+}
+
+// `LANGUAGE_LIST`, `EXTENSION_LIST`, `FILENAME_LIST` and `ALIAS_LIST`,
+// generated by build.rs from data/languages.toml.
+include!(concat!(env!("OUT_DIR"), "/languages.rs"));
 
 lazy_static! {
-    static ref EXTENSIONS: HashMap<&'static str, &'static str> = {
-        let mut m = HashMap::new();
-        m.insert("rs", "Rust");
-        m.insert("go", "Go");
-        m.insert("js", "JavaScript");
-        m.insert("py", "Python");
-        m.insert("java", "Java");
-        m.insert("c", "C");
-        m.insert("cpp", "C++");
-        m.insert("h", "C/C++ Header");
-        m.insert("hpp", "C++ Header");
-        m.insert("cs", "C#");
-        m.insert("html", "HTML");
-        m.insert("css", "CSS");
-        m.insert("md", "Markdown");
-        m.insert("json", "JSON");
-        m.insert("yml", "YAML");
-        m.insert("yaml", "YAML");
-        m.insert("xml", "XML");
-        m.insert("sh", "Shell Script");
-        m.insert("bat", "Batch Script");
-        m.insert("ps1", "PowerShell Script");
-        m.insert("sql", "SQL");
-        m.insert("rb", "Ruby");
-        m.insert("php", "PHP");
-        m.insert("swift", "Swift");
-        m.insert("kt", "Kotlin");
-        m.insert("ts", "TypeScript");
-        m.insert("scala", "Scala");
-        m.insert("lua", "Lua");
-        m.insert("pl", "Perl");
-        m.insert("r", "R");
-        m.insert("dart", "Dart");
-        m.insert("erl", "Erlang");
-        m.insert("ex", "Elixir");
-        m.insert("hs", "Haskell");
-        m
+    static ref EXTENSIONS: HashMap<&'static str, &'static str> =
+        EXTENSION_LIST.iter().copied().collect();
+    /// Files recognised by their whole name, matched case-insensitively.
+    static ref FILENAMES: HashMap<&'static str, &'static str> =
+        FILENAME_LIST.iter().copied().collect();
+    /// Interpreter names used in shebang lines and mode names used in emacs
+    /// and vim modelines, lower-cased.
+    static ref ALIASES: HashMap<&'static str, &'static str> =
+        ALIAS_LIST.iter().copied().collect();
+    static ref LANGUAGES: HashMap<&'static str, &'static Language> =
+        LANGUAGE_LIST.iter().map(|lang| (lang.name, lang)).collect();
+}
+
+/// Languages and mappings from the config files, consulted before the
+/// built-in tables.
+#[derive(Default)]
+struct Overrides {
+    languages: HashMap<String, &'static Language>,
+    extensions: HashMap<String, String>,
+    filenames: HashMap<String, String>,
+}
+
+static OVERRIDES: OnceLock<Overrides> = OnceLock::new();
+
+/// Installs the languages and mappings from `config` on top of the built-in
+/// tables. Must be called before the scan starts; later calls are ignored.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let _ = OVERRIDES.set(Overrides::new(config)?);
+    Ok(())
+}
+
+impl Overrides {
+    fn new(config: &Config) -> Result<Overrides, String> {
+        let mut overrides = Overrides::default();
+        for (name, language) in &config.languages {
+            let built_in = LANGUAGES.get(name.as_str()).copied();
+            overrides
+                .languages
+                .insert(name.clone(), configured_language(name, language, built_in));
+            for extension in &language.extensions {
+                overrides
+                    .extensions
+                    .insert(extension.trim_start_matches('.').to_string(), name.clone());
+            }
+            for filename in &language.filenames {
+                overrides.filenames.insert(filename.to_lowercase(), name.clone());
+            }
+        }
+        for (extension, name) in &config.extensions {
+            overrides
+                .extensions
+                .insert(extension.trim_start_matches('.').to_string(), name.clone());
+        }
+        for (filename, name) in &config.filenames {
+            overrides.filenames.insert(filename.to_lowercase(), name.clone());
+        }
+
+        let known =
+            |name: &str| overrides.languages.contains_key(name) || LANGUAGES.contains_key(name);
+        let mappings = overrides.extensions.iter().chain(&overrides.filenames);
+        for (key, name) in mappings {
+            if !known(name) {
+                return Err(format!("Unknown language '{}' for '{}' in config", name, key));
+            }
+        }
+
+        Ok(overrides)
+    }
+}
+
+/// Builds a language from its config entry, keeping the built-in syntax for
+/// anything the entry leaves out. The strings are leaked, as languages live
+/// for the rest of the run.
+fn configured_language(
+    name: &str,
+    config: &LanguageConfig,
+    built_in: Option<&'static Language>,
+) -> &'static Language {
+    let line_comments = match &config.line_comments {
+        Some(markers) => Box::leak(markers.iter().map(|m| leak(m)).collect()),
+        None => built_in.map_or(&[][..], |lang| lang.line_comments),
+    };
+    let block_comments = match &config.block_comments {
+        Some(pairs) => leak_pairs(pairs),
+        None => built_in.map_or(&[][..], |lang| lang.block_comments),
+    };
+    let quotes = match &config.quotes {
+        Some(pairs) => leak_pairs(pairs),
+        None => built_in.map_or(&[][..], |lang| lang.quotes),
     };
+    let nested_comments = config
+        .nested_comments
+        .unwrap_or(built_in.is_some_and(|lang| lang.nested_comments));
+    let char_literals = config
+        .char_literals
+        .unwrap_or(built_in.is_some_and(|lang| lang.char_literals));
+    let category = config
+        .category
+        .or(built_in.map(|lang| lang.category))
+        .unwrap_or_default();
+    Box::leak(Box::new(language(
+        leak(name),
+        category,
+        line_comments,
+        block_comments,
+        nested_comments,
+        quotes,
+        char_literals,
+    )))
+}
+
+fn leak(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+fn leak_pairs(pairs: &[(String, String)]) -> &'static [(&'static str, &'static str)] {
+    Box::leak(pairs.iter().map(|(open, close)| (leak(open), leak(close))).collect())
+}
+
+/// The installed overrides, or none before `configure` is called.
+fn overrides() -> &'static Overrides {
+    static NONE: OnceLock<Overrides> = OnceLock::new();
+    OVERRIDES
+        .get()
+        .unwrap_or_else(|| NONE.get_or_init(Overrides::default))
+}
+
+pub fn get_language(name: &str) -> Option<&'static Language> {
+    overrides().get_language(name)
+}
+
+/// Resolves a language name given on the command line: an exact name,
+/// then a name in any case, then an interpreter or mode name such as `py`.
+pub fn find_language(name: &str) -> Option<&'static Language> {
+    get_language(name)
+        .or_else(|| {
+            overrides()
+                .languages
+                .values()
+                .chain(LANGUAGES.values())
+                .copied()
+                .find(|lang| lang.name.eq_ignore_ascii_case(name))
+        })
+        .or_else(|| lookup_alias(name))
+}
+
+/// The category of the language called `name`, `Programming` if unknown.
+pub fn category_of(name: &str) -> Category {
+    get_language(name).map_or(Category::default(), |lang| lang.category)
+}
+
+/// Which step of the detection pipeline recognised a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionRule {
+    #[default]
+    Filename,
+    Shebang,
+    Modeline,
+    Heuristic,
+    Extension,
+}
+
+impl fmt::Display for DetectionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DetectionRule::Filename => "file name",
+            DetectionRule::Shebang => "shebang",
+            DetectionRule::Modeline => "modeline",
+            DetectionRule::Heuristic => "heuristic",
+            DetectionRule::Extension => "extension",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    pub language: &'static Language,
+    pub rule: DetectionRule,
+    /// What the content heuristic matched, when `rule` is `Heuristic`.
+    pub heuristic: Option<&'static str>,
+}
+
+/// Number of lines at the start and end of a file searched for a vim
+/// modeline, matching vim's default `modelines` setting.
+const MODELINE_LINES: usize = 5;
+
+/// Detects the language of `path` from, in order: its exact file name, a
+/// shebang line, an emacs or vim modeline, content heuristics for extensions
+/// shared by several languages, and its extension. `content` may be the whole
+/// file or only its beginning. `siblings` lists the files next to `path`
+/// for the heuristics.
+pub fn detect(path: &Path, content: &[u8], siblings: &Siblings) -> Option<Detection> {
+    overrides().detect(path, content, siblings)
+}
+
+pub fn detect_by_filename(path: &Path) -> Option<&'static Language> {
+    overrides().detect_by_filename(path)
+}
+
+pub fn detect_by_extension(path: &Path) -> Option<&'static Language> {
+    overrides().detect_by_extension(path)
+}
+
+/// Detection against a given set of overrides, which the free functions
+/// above resolve against the installed ones.
+impl Overrides {
+    fn get_language(&self, name: &str) -> Option<&'static Language> {
+        self.languages
+            .get(name)
+            .copied()
+            .or_else(|| LANGUAGES.get(name).copied())
+    }
+
+    fn detect(&self, path: &Path, content: &[u8], siblings: &Siblings) -> Option<Detection> {
+        let by = |rule, language: Option<&'static Language>| {
+            language.map(|language| Detection {
+                language,
+                rule,
+                heuristic: None,
+            })
+        };
+        by(DetectionRule::Filename, self.detect_by_filename(path))
+            .or_else(|| by(DetectionRule::Shebang, detect_by_shebang(content)))
+            .or_else(|| by(DetectionRule::Modeline, detect_by_modeline(content)))
+            .or_else(|| self.detect_by_heuristic(path, content, siblings))
+            .or_else(|| by(DetectionRule::Extension, self.detect_by_extension(path)))
+    }
+
+    /// Settles extensions such as `.h` or `.pl` that several languages
+    /// share, unless the config maps the extension explicitly.
+    fn detect_by_heuristic(
+        &self,
+        path: &Path,
+        content: &[u8],
+        siblings: &Siblings,
+    ) -> Option<Detection> {
+        if self.configured_extension(path).is_some() {
+            return None;
+        }
+        let heuristic = heuristics::disambiguate(path, content, siblings)?;
+        Some(Detection {
+            language: self.get_language(heuristic.language)?,
+            rule: DetectionRule::Heuristic,
+            heuristic: Some(heuristic.reason),
+        })
+    }
+
+    fn detect_by_filename(&self, path: &Path) -> Option<&'static Language> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        self.filenames
+            .get(&name)
+            .map(String::as_str)
+            .or_else(|| FILENAMES.get(name.as_str()).copied())
+            .and_then(|name| self.get_language(name))
+    }
+
+    fn detect_by_extension(&self, path: &Path) -> Option<&'static Language> {
+        let extension = path.extension()?.to_str()?;
+        self.configured_extension(path)
+            .or_else(|| EXTENSIONS.get(extension).copied())
+            .or_else(|| EXTENSIONS.get(extension.to_lowercase().as_str()).copied())
+            .and_then(|name| self.get_language(name))
+    }
+
+    /// The language name the config maps the extension of `path` to.
+    fn configured_extension(&self, path: &Path) -> Option<&str> {
+        let extension = path.extension()?.to_str()?;
+        self.extensions
+            .get(extension)
+            .or_else(|| self.extensions.get(&extension.to_lowercase()))
+            .map(String::as_str)
+    }
+}
+
+/// Recognises `#!/usr/bin/python3`, `#!/usr/bin/env -S node --flag` and
+/// similar interpreter lines.
+pub fn detect_by_shebang(content: &[u8]) -> Option<&'static Language> {
+    let first_line = content.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(first_line.strip_prefix(b"#!")?).ok()?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    lookup_alias(interpreter)
+}
+
+/// Recognises `-*- mode: python -*-` style emacs modelines on the first two
+/// lines and `vim: set ft=python:` style vim modelines near the start or end
+/// of the file.
+pub fn detect_by_modeline(content: &[u8]) -> Option<&'static Language> {
+    let text = String::from_utf8_lossy(content);
+    let lines: Vec<&str> = text.lines().collect();
+
+    if let Some(language) = lines.iter().take(2).find_map(|line| emacs_mode(line)) {
+        return Some(language);
+    }
+
+    let tail_start = lines.len().saturating_sub(MODELINE_LINES).max(MODELINE_LINES);
+    lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(tail_start))
+        .find_map(|line| vim_filetype(line))
+}
+
+fn emacs_mode(line: &str) -> Option<&'static Language> {
+    let start = line.find("-*-")? + 3;
+    let end = start + line[start..].find("-*-")?;
+    let vars = line[start..end].trim();
+    if !vars.contains(':') {
+        return lookup_alias(vars);
+    }
+    vars.split(';').find_map(|var| {
+        let (key, value) = var.split_once(':')?;
+        if key.trim().eq_ignore_ascii_case("mode") {
+            lookup_alias(value.trim())
+        } else {
+            None
+        }
+    })
+}
+
+fn vim_filetype(line: &str) -> Option<&'static Language> {
+    let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|(i, _)| *i == 0 || line[..*i].ends_with(char::is_whitespace))
+            .map(|(i, _)| i + marker.len())
+    })?;
+    line[start..]
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .find_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            match key {
+                "ft" | "filetype" | "syntax" | "syn" => lookup_alias(value),
+                _ => None,
+            }
+        })
 }
 
-pub fn get_language_name(extension: &str) -> Option<String> {
-    EXTENSIONS.get(extension).map(|&s| s.to_string())
+/// Looks up an interpreter or mode name, ignoring case and trailing version
+/// numbers such as the `3.11` in `python3.11`.
+fn lookup_alias(name: &str) -> Option<&'static Language> {
+    let name = name.to_lowercase();
+    let name = name.strip_suffix("-mode").unwrap_or(&name);
+    let unversioned = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    ALIASES
+        .get(name)
+        .or_else(|| ALIASES.get(unversioned))
+        .and_then(|name| get_language(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(path: &str, content: &str) -> Option<(&'static str, DetectionRule)> {
+        let siblings = Siblings::disk();
+        detect(Path::new(path), content.as_bytes(), &siblings).map(|d| (d.language.name, d.rule))
+    }
+
+    #[test]
+    fn file_names() {
+        let by_name = |name| Some((name, DetectionRule::Filename));
+        assert_eq!(detected("Makefile", ""), by_name("Makefile"));
+        assert_eq!(detected("src/makefile", ""), by_name("Makefile"));
+        assert_eq!(detected("Dockerfile", ""), by_name("Dockerfile"));
+        assert_eq!(detected("CMakeLists.txt", ""), by_name("CMake"));
+        assert_eq!(detected("ci/Jenkinsfile", ""), by_name("Groovy"));
+    }
+
+    #[test]
+    fn shebangs() {
+        let by_shebang = |name| Some((name, DetectionRule::Shebang));
+        assert_eq!(detected("run", "#!/usr/bin/env -S python3 -u\n"), by_shebang("Python"));
+        assert_eq!(detected("run", "#!/bin/bash\necho hi\n"), by_shebang("Shell Script"));
+        assert_eq!(detected("run", "#!/usr/bin/python3.11\n"), by_shebang("Python"));
+        // The interpreter wins over the extension.
+        assert_eq!(detected("build.sh", "#!/usr/bin/env ruby\n"), by_shebang("Ruby"));
+        assert_eq!(detected("run", "#!/usr/bin/env\n"), None);
+    }
+
+    #[test]
+    fn emacs_modelines() {
+        let by_modeline = |name| Some((name, DetectionRule::Modeline));
+        assert_eq!(detected("tool", "# -*- mode: ruby -*-\n"), by_modeline("Ruby"));
+        assert_eq!(detected("tool", "#!/bin/true\n# -*- python -*-\n"), by_modeline("Python"));
+        assert_eq!(
+            detected("tool", "; -*- coding: utf-8; mode: Python -*-\n"),
+            by_modeline("Python")
+        );
+        // Only the first two lines are searched.
+        assert_eq!(detected("tool", "\n\n# -*- mode: ruby -*-\n"), None);
+    }
+
+    #[test]
+    fn vim_modelines() {
+        let by_modeline = |name| Some((name, DetectionRule::Modeline));
+        assert_eq!(detected("tool", "# vim: set ft=python:\n"), by_modeline("Python"));
+        assert_eq!(detected("tool", "# vi: filetype=ruby\n"), by_modeline("Ruby"));
+        let tail = format!("{}# vim: ft=sh\n", "x\n".repeat(20));
+        assert_eq!(detected("tool", &tail), by_modeline("Shell Script"));
+        let middle = format!("{}# vim: ft=sh\n{}", "x\n".repeat(10), "x\n".repeat(10));
+        assert_eq!(detected("tool", &middle), None);
+        // `vim:` has to start a word.
+        assert_eq!(detected("tool", "# novim: ft=sh\n"), None);
+    }
+
+    #[test]
+    fn extension_fallback() {
+        let by_extension = |name| Some((name, DetectionRule::Extension));
+        assert_eq!(detected("src/lib.rs", "fn main() {}\n"), by_extension("Rust"));
+        assert_eq!(detected("MAIN.RS", ""), by_extension("Rust"));
+        assert_eq!(detected("app.py", "print(1)\n"), by_extension("Python"));
+        assert_eq!(detected("notes.unknown", "text\n"), None);
+        assert_eq!(detected("README", "text\n"), None);
+    }
+
+    fn configured(toml: &str) -> Result<Overrides, String> {
+        Overrides::new(&toml::from_str(toml).unwrap())
+    }
+
+    fn detected_with(
+        overrides: &Overrides,
+        path: &str,
+        content: &str,
+    ) -> Option<(&'static str, DetectionRule)> {
+        let siblings = Siblings::disk();
+        overrides
+            .detect(Path::new(path), content.as_bytes(), &siblings)
+            .map(|d| (d.language.name, d.rule))
+    }
+
+    #[test]
+    fn config_mappings_beat_built_in_ones() {
+        let overrides = configured(
+            "[extensions]\nm = \"MATLAB\"\n\".tmpl\" = \"HTML\"\n[filenames]\nBUILD = \"Python\"\n",
+        )
+        .unwrap();
+        let objective_c = "#import <Foundation/Foundation.h>\n";
+        assert_eq!(
+            detected_with(&overrides, "x.m", objective_c),
+            Some(("MATLAB", DetectionRule::Extension))
+        );
+        assert_eq!(
+            detected_with(&Overrides::default(), "x.m", objective_c),
+            Some(("Objective-C", DetectionRule::Heuristic))
+        );
+        assert_eq!(
+            detected_with(&overrides, "page.tmpl", ""),
+            Some(("HTML", DetectionRule::Extension))
+        );
+        assert_eq!(
+            detected_with(&overrides, "pkg/build", ""),
+            Some(("Python", DetectionRule::Filename))
+        );
+        // Shebangs still come before extensions.
+        assert_eq!(
+            detected_with(&overrides, "x.m", "#!/bin/bash\n"),
+            Some(("Shell Script", DetectionRule::Shebang))
+        );
+    }
+
+    #[test]
+    fn configured_languages() {
+        let overrides = configured(
+            r##"
+            [languages.Protobuf]
+            category = "data"
+            line_comments = ["//"]
+            extensions = ["proto"]
+
+            [languages.Rust]
+            line_comments = ["#"]
+            "##,
+        )
+        .unwrap();
+        let protobuf = overrides.get_language("Protobuf").unwrap();
+        assert_eq!(protobuf.category, Category::Data);
+        assert_eq!(protobuf.line_comments, ["//"]);
+        assert!(protobuf.block_comments.is_empty());
+        assert_eq!(
+            detected_with(&overrides, "api/v1.proto", ""),
+            Some(("Protobuf", DetectionRule::Extension))
+        );
+
+        // Fields a built-in language's entry leaves out keep their values.
+        let rust = overrides.get_language("Rust").unwrap();
+        assert_eq!(rust.line_comments, ["#"]);
+        assert_eq!(rust.block_comments, [("/*", "*/")]);
+        assert!(rust.nested_comments && rust.char_literals);
+        assert_eq!(rust.category, Category::Programming);
+    }
+
+    #[test]
+    fn unknown_languages_in_config() {
+        let error = configured("[extensions]\nfoo = \"Nope\"\n").err();
+        assert_eq!(error.as_deref(), Some("Unknown language 'Nope' for 'foo' in config"));
+        let error = configured("[filenames]\nRules = \"Klingon\"\n").err();
+        assert_eq!(error.as_deref(), Some("Unknown language 'Klingon' for 'rules' in config"));
+        let error = configured("[languages.Protobuf]\nextensions = [\"proto\"]\n").err();
+        assert_eq!(error, None);
+    }
 }
@@ -1,99 +1,512 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
-use std::fs;
+use std::fmt;
+use std::io;
 use std::path::Path;
 
+use counter::LineCounts;
+use encoding_rs::Encoding;
+use git::BlameHunk;
+use git2::Oid;
+use identity::Identities;
+use languages::{Category, DetectionRule, Language};
+use serde::{Serialize, Serializer};
+use skip::SkipReason;
+
 mod cmd;
+mod config;
+mod counter;
+mod encoding;
+mod filter;
 mod git;
+mod heuristics;
+mod identity;
 mod languages;
+mod output;
+mod scan;
+mod skip;
 mod tui;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ContributorInfo {
     pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
     pub files: usize,
+    #[serde(flatten)]
+    pub authorship: Authorship,
 }
 
-#[derive(Debug, Clone, Default)]
+/// Where a contributor's lines come from, as seen in blame.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Authorship {
+    /// The emails their lines were committed under, as mapped by `.mailmap`.
+    pub emails: BTreeSet<String>,
+    /// Author time of the oldest commit any of their lines come from, in
+    /// seconds since the Unix epoch; `None` if none of them are committed.
+    pub first_commit: Option<i64>,
+    /// Author time of the newest commit any of their lines come from.
+    pub last_commit: Option<i64>,
+    /// The distinct commits their lines come from, written as a count.
+    #[serde(rename = "commits", serialize_with = "serialize_len")]
+    pub commit_ids: HashSet<Oid>,
+}
+
+impl Authorship {
+    fn add(&mut self, hunk: &BlameHunk) {
+        if !hunk.email.is_empty() {
+            self.emails.insert(hunk.email.to_lowercase());
+        }
+        if let Some((id, time)) = hunk.commit {
+            self.commit_ids.insert(id);
+            self.first_commit = Some(self.first_commit.map_or(time, |first| first.min(time)));
+            self.last_commit = Some(self.last_commit.map_or(time, |last| last.max(time)));
+        }
+    }
+
+    fn merge(&mut self, other: &Authorship) {
+        self.emails.extend(other.emails.iter().cloned());
+        self.commit_ids.extend(&other.commit_ids);
+        self.first_commit = [self.first_commit, other.first_commit].into_iter().flatten().min();
+        self.last_commit = [self.last_commit, other.last_commit].into_iter().flatten().max();
+    }
+
+    /// Number of distinct commits the lines come from.
+    pub fn commits(&self) -> usize {
+        self.commit_ids.len()
+    }
+}
+
+fn serialize_len<S: Serializer>(ids: &HashSet<Oid>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(ids.len() as u64)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct LanguageInfo {
     pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
     pub files: usize,
 }
 
+impl ContributorInfo {
+    fn add_counts(&mut self, counts: &LineCounts) {
+        self.lines += counts.lines();
+        self.code += counts.code;
+        self.comments += counts.comments;
+        self.blanks += counts.blanks;
+    }
+
+    fn merge(&mut self, other: &ContributorInfo) {
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.files += other.files;
+        self.authorship.merge(&other.authorship);
+    }
+}
+
+impl LanguageInfo {
+    fn add_counts(&mut self, counts: &LineCounts) {
+        self.lines += counts.lines();
+        self.code += counts.code;
+        self.comments += counts.comments;
+        self.blanks += counts.blanks;
+    }
+
+    fn merge(&mut self, other: &LanguageInfo) {
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.files += other.files;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Walk every file, disregarding ignore files and git excludes.
+    pub no_ignore: bool,
+    /// Number of worker threads; `0` picks one per available core.
+    pub threads: usize,
+    /// Only count languages in these categories; empty counts all of them.
+    pub categories: Vec<Category>,
+    /// Only count these languages, by name; empty counts all of them.
+    pub languages: Vec<&'static str>,
+    /// Only walk paths matching one of these globs, if any are given.
+    pub include: Vec<String>,
+    /// Never walk paths matching any of these globs.
+    pub exclude: Vec<String>,
+    /// How many directories deep to walk below the root; unlimited when
+    /// `None`.
+    pub max_depth: Option<usize>,
+    /// Count files marked as generated, and lockfiles.
+    pub include_generated: bool,
+    /// Count minified files.
+    pub include_minified: bool,
+    /// Count files under `vendor/`, `third_party/`, `node_modules/` and the like.
+    pub include_vendored: bool,
+    /// Abort on the first unreadable file or git error instead of recording
+    /// it in the `ScanReport`.
+    pub strict: bool,
+    /// Encoding of files without a byte order mark; detected per file when
+    /// `None`.
+    pub encoding: Option<&'static Encoding>,
+    /// Count and blame the files in the tree of this commit, branch or tag
+    /// instead of the working directory.
+    pub rev: Option<String>,
+    /// Revisions whose changes blame looks past, crediting each line to its
+    /// previous author, on top of those in `.git-blame-ignore-revs`.
+    pub ignore_revs: Vec<String>,
+    /// Who blamed lines are credited to, from `--group-by` and the config's
+    /// aliases.
+    pub identities: Identities,
+}
+
+impl ScanOptions {
+    /// Whether files in `language` should be counted.
+    pub fn includes_language(&self, language: &Language) -> bool {
+        (self.categories.is_empty() || self.categories.contains(&language.category))
+            && (self.languages.is_empty() || self.languages.contains(&language.name))
+    }
+
+    /// Whether files skipped for `reason` are counted anyway. Binary files
+    /// never are, as they have no lines to count.
+    pub fn includes_skipped(&self, reason: SkipReason) -> bool {
+        match reason {
+            SkipReason::Binary => false,
+            SkipReason::Generated => self.include_generated,
+            SkipReason::Minified => self.include_minified,
+            SkipReason::Vendored => self.include_vendored,
+        }
+    }
+}
+
+/// The languages of one category with their subtotal, see
+/// `Stats::categories`.
+#[derive(Debug)]
+pub struct CategoryGroup<'a> {
+    pub category: Category,
+    pub totals: LanguageInfo,
+    /// Languages in display order.
+    pub languages: Vec<(&'a String, &'a LanguageInfo)>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileInfo {
+    pub path: String,
+    pub language: String,
+    /// How `language` was determined.
+    pub detected_by: DetectionRule,
+    /// What the content heuristic matched, when `detected_by` is `heuristic`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heuristic: Option<&'static str>,
+    pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+    /// The encoding the file was read in, e.g. `UTF-8` or `Shift_JIS`.
+    pub encoding: &'static str,
+    pub bytes: u64,
+    /// Lines attributed to each contributor.
+    pub contributors: BTreeMap<String, usize>,
+}
+
+impl FileInfo {
+    /// The contributor owning the most lines of this file.
+    pub fn top_contributor(&self) -> Option<&str> {
+        self.contributors
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// A file in a recognised language that was not counted.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+    pub bytes: u64,
+}
+
+/// What kind of error a `Problem` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// A file or directory could not be read for lack of permission.
+    PermissionDenied,
+    /// Any other I/O error while reading a file or listing a directory.
+    Io,
+    /// An ignore file could not be parsed, or a symlink loop was found.
+    Walk,
+    /// The repository could not be opened or a file could not be blamed.
+    Git,
+}
+
+impl ProblemKind {
+    pub fn of_io(error: &io::Error) -> ProblemKind {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => ProblemKind::PermissionDenied,
+            _ => ProblemKind::Io,
+        }
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ProblemKind::PermissionDenied => "permission denied",
+            ProblemKind::Io => "I/O error",
+            ProblemKind::Walk => "walk error",
+            ProblemKind::Git => "git error",
+        })
+    }
+}
+
+/// A file or directory that could not be scanned, or only partly. Files with
+/// a problem reading them are left out of the counts; files that could not be
+/// blamed are counted with an unknown contributor.
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    /// Written like `FileInfo::path`; empty when the error has no path.
+    pub path: String,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Everything that went wrong during a scan that did not stop it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanReport {
+    pub problems: Vec<Problem>,
+}
+
+impl ScanReport {
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Problems sorted by path, then kind.
+    pub fn sorted_problems(&self) -> Vec<&Problem> {
+        let mut problems: Vec<_> = self.problems.iter().collect();
+        problems.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.kind.cmp(&b.kind)));
+        problems
+    }
+}
+
+/// Totals for every file below a directory, at any depth.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectoryInfo {
+    pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+    pub files: usize,
+    pub languages: BTreeMap<String, LanguageInfo>,
+    pub contributors: BTreeMap<String, ContributorInfo>,
+}
+
+impl DirectoryInfo {
+    fn merge(&mut self, other: &DirectoryInfo) {
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.files += other.files;
+        for (name, info) in &other.languages {
+            self.languages.entry(name.clone()).or_default().merge(info);
+        }
+        for (name, info) in &other.contributors {
+            self.contributors.entry(name.clone()).or_default().merge(info);
+        }
+    }
+}
+
+/// One of the paths given on the command line, with its own totals.
+#[derive(Debug, Clone, Serialize)]
+pub struct RootInfo {
+    pub path: String,
+    /// The commit contributors were blamed at, if the path is in a git
+    /// repository.
+    pub commit: Option<String>,
+    #[serde(flatten)]
+    pub totals: LanguageInfo,
+}
+
+/// Where and when a scan was run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanMeta {
+    /// The scanned directory, or the directory containing every scanned path.
+    pub root: String,
+    /// The commit contributors were blamed at, if every root is in a git
+    /// repository at the same commit.
+    pub commit: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub scanned_at: u64,
+    /// Key of the scan root in `Stats::directories`.
+    #[serde(skip)]
+    pub root_key: String,
+}
+
 #[derive(Debug, Default)]
 pub struct Stats {
     pub languages: HashMap<String, LanguageInfo>,
     pub contributors: HashMap<String, ContributorInfo>,
+    pub files: Vec<FileInfo>,
+    /// Rollups keyed by directory path, written like `FileInfo::path`. The
+    /// scan root itself is keyed by `ScanMeta::root_key`.
+    pub directories: BTreeMap<String, DirectoryInfo>,
+    /// Recognised files left out of all counts.
+    pub skipped: Vec<SkippedFile>,
+    /// Errors that were recorded instead of aborting the scan.
+    pub report: ScanReport,
+    /// The scanned paths, in command-line order.
+    pub roots: Vec<RootInfo>,
+    pub meta: ScanMeta,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    cmd::execute()
-}
+impl Stats {
+    /// Folds the totals of `other` into `self`. Merging is order-independent,
+    /// so per-thread results can be combined in any order.
+    fn merge(&mut self, other: Stats) {
+        for (name, info) in other.languages {
+            self.languages.entry(name).or_default().merge(&info);
+        }
+        for (name, info) in other.contributors {
+            self.contributors.entry(name).or_default().merge(&info);
+        }
+        self.files.extend(other.files);
+        for (path, info) in other.directories {
+            self.directories.entry(path).or_default().merge(&info);
+        }
+        self.skipped.extend(other.skipped);
+        self.report.problems.extend(other.report.problems);
+        self.roots.extend(other.roots);
+    }
 
-pub fn get_stats(path: &str) -> Result<Stats, Box<dyn Error>> {
-    let mut stats = Stats::default();
-    process_directory(Path::new(path), &mut stats)?;
-    Ok(stats)
-}
+    /// Shortens contributors keyed `Name <email>` to just the name wherever
+    /// no other contributor has it, in every table that lists contributors.
+    fn shorten_contributor_names(&mut self) {
+        let names = identity::display_names(self.contributors.keys().map(String::as_str));
+        if names.is_empty() {
+            return;
+        }
+        let rename = |key: String| names.get(&key).cloned().unwrap_or(key);
+        self.contributors = self
+            .contributors
+            .drain()
+            .map(|(name, info)| (rename(name), info))
+            .collect();
+        for file in &mut self.files {
+            file.contributors = std::mem::take(&mut file.contributors)
+                .into_iter()
+                .map(|(name, lines)| (rename(name), lines))
+                .collect();
+        }
+        for directory in self.directories.values_mut() {
+            directory.contributors = std::mem::take(&mut directory.contributors)
+                .into_iter()
+                .map(|(name, info)| (rename(name), info))
+                .collect();
+        }
+    }
 
-fn process_directory(dir: &Path, stats: &mut Stats) -> Result<(), Box<dyn Error>> {
-    if !dir.is_dir() {
-        return Err(format!("The path '{}' is not a directory.", dir.display()).into());
+    /// Languages in display order: most lines first, ties broken by name.
+    pub fn sorted_languages(&self) -> Vec<(&String, &LanguageInfo)> {
+        let mut languages: Vec<_> = self.languages.iter().collect();
+        languages.sort_by(|a, b| b.1.lines.cmp(&a.1.lines).then_with(|| a.0.cmp(b.0)));
+        languages
     }
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+    /// Contributors in display order: most lines first, ties broken by name.
+    pub fn sorted_contributors(&self) -> Vec<(&String, &ContributorInfo)> {
+        let mut contributors: Vec<_> = self.contributors.iter().collect();
+        contributors.sort_by(|a, b| b.1.lines.cmp(&a.1.lines).then_with(|| a.0.cmp(b.0)));
+        contributors
+    }
+
+    /// Files in display order: most lines first, ties broken by path.
+    pub fn sorted_files(&self) -> Vec<&FileInfo> {
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.path.cmp(&b.path)));
+        files
+    }
 
-        if path.is_file() && is_valid_file(&path) {
-            process_file(&path, stats)?;
-        } else if path.is_dir() {
-            process_directory(&path, stats)?;
+    /// Subtotals per language category, in category order. Categories
+    /// without any files are left out.
+    pub fn categories(&self) -> Vec<CategoryGroup<'_>> {
+        let mut groups: BTreeMap<Category, CategoryGroup> = BTreeMap::new();
+        for (name, info) in self.sorted_languages() {
+            let category = languages::category_of(name);
+            let group = groups.entry(category).or_insert_with(|| CategoryGroup {
+                category,
+                totals: LanguageInfo::default(),
+                languages: Vec::new(),
+            });
+            group.totals.merge(info);
+            group.languages.push((name, info));
         }
+        groups.into_values().collect()
     }
-    Ok(())
-}
 
-fn process_file(path: &Path, stats: &mut Stats) -> Result<(), Box<dyn Error>> {
-    if let Some(language) = get_language_name(path) {
-        let content = fs::read_to_string(path)?;
-        let lines = content.lines().count();
+    /// Skipped files sorted by path.
+    pub fn sorted_skipped(&self) -> Vec<&SkippedFile> {
+        let mut skipped: Vec<_> = self.skipped.iter().collect();
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        skipped
+    }
 
-        let lang_info = stats.languages.entry(language.clone()).or_default();
-        lang_info.lines += lines;
-        lang_info.files += 1;
+    /// Number of skipped files for each reason that occurred.
+    pub fn skipped_counts(&self) -> BTreeMap<SkipReason, usize> {
+        let mut counts = BTreeMap::new();
+        for file in &self.skipped {
+            *counts.entry(file.reason).or_default() += 1;
+        }
+        counts
+    }
 
-        match git::get_file_info(path) {
-            Ok((_, file_contributors)) => {
-                for (contributor, contributor_lines) in file_contributors {
-                    update_contributor_info(&mut stats.contributors, &contributor, contributor_lines);
-                }
-            }
-            Err(e) => {
-                eprintln!("Git error for file {}: {}", path.display(), e);
-                update_contributor_info(&mut stats.contributors, "Unknown", lines);
-            }
+    pub fn totals(&self) -> LanguageInfo {
+        let mut totals = LanguageInfo::default();
+        for info in self.languages.values() {
+            totals.merge(info);
         }
+        totals
     }
-    Ok(())
 }
 
-fn update_contributor_info(
-    contributors: &mut HashMap<String, ContributorInfo>,
-    contributor: &str,
-    lines: usize,
-) {
-    let contrib_info = contributors.entry(contributor.to_string()).or_default();
-    contrib_info.lines += lines;
-    contrib_info.files += 1;
+/// Direct subdirectories of `dir` in `directories`, most lines first.
+pub fn subdirectories<'a>(
+    directories: &'a BTreeMap<String, DirectoryInfo>,
+    dir: &str,
+) -> Vec<(&'a String, &'a DirectoryInfo)> {
+    let mut children: Vec<_> = directories
+        .iter()
+        .filter(|(path, _)| {
+            path.as_str() != dir && Path::new(path).parent() == Some(Path::new(dir))
+        })
+        .collect();
+    children.sort_by(|a, b| b.1.lines.cmp(&a.1.lines).then_with(|| a.0.cmp(b.0)));
+    children
 }
 
-fn is_valid_file(path: &Path) -> bool {
-    path.extension().is_some()
+fn main() -> Result<(), Box<dyn Error>> {
+    cmd::execute()
 }
 
-fn get_language_name(path: &Path) -> Option<String> {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .and_then(|ext| languages::get_language_name(ext))
+pub fn get_stats(paths: &[&str], options: &ScanOptions) -> Result<Stats, Box<dyn Error>> {
+    let paths: Vec<&Path> = paths.iter().map(Path::new).collect();
+    scan::process_paths(&paths, options)
 }
@@ -0,0 +1,141 @@
+use std::error::Error;
+use std::io::Write;
+
+use super::{format_date, Table};
+use crate::Stats;
+
+/// Writes one table as delimiter-separated rows, with the same columns and
+/// row order as the TUI. Fields containing the delimiter, a quote or a line
+/// break are quoted and embedded quotes are doubled, as in RFC 4180.
+pub fn write(
+    stats: &Stats,
+    table: Table,
+    delimiter: char,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let (header, rows): (&[&str], Vec<Vec<String>>) = match table {
+        Table::Languages => (
+            &["Language", "Lines", "Code", "Comments", "Blanks", "Files"],
+            stats
+                .sorted_languages()
+                .into_iter()
+                .map(|(name, info)| {
+                    vec![
+                        name.clone(),
+                        info.lines.to_string(),
+                        info.code.to_string(),
+                        info.comments.to_string(),
+                        info.blanks.to_string(),
+                        info.files.to_string(),
+                    ]
+                })
+                .collect(),
+        ),
+        Table::Contributors => (
+            &[
+                "Contributor",
+                "Lines",
+                "Code",
+                "Comments",
+                "Blanks",
+                "Files",
+                "Emails",
+                "Commits",
+                "First Commit",
+                "Last Commit",
+            ],
+            stats
+                .sorted_contributors()
+                .into_iter()
+                .map(|(name, info)| {
+                    let authorship = &info.authorship;
+                    vec![
+                        name.clone(),
+                        info.lines.to_string(),
+                        info.code.to_string(),
+                        info.comments.to_string(),
+                        info.blanks.to_string(),
+                        info.files.to_string(),
+                        authorship.emails.iter().cloned().collect::<Vec<_>>().join(" "),
+                        authorship.commits().to_string(),
+                        authorship.first_commit.map(format_date).unwrap_or_default(),
+                        authorship.last_commit.map(format_date).unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        ),
+        Table::Files => (
+            &[
+                "File",
+                "Language",
+                "Lines",
+                "Code",
+                "Comments",
+                "Blanks",
+                "Bytes",
+                "Top Contributor",
+            ],
+            stats
+                .sorted_files()
+                .into_iter()
+                .map(|file| {
+                    vec![
+                        file.path.clone(),
+                        file.language.clone(),
+                        file.lines.to_string(),
+                        file.code.to_string(),
+                        file.comments.to_string(),
+                        file.blanks.to_string(),
+                        file.bytes.to_string(),
+                        file.top_contributor().unwrap_or_default().to_string(),
+                    ]
+                })
+                .collect(),
+        ),
+        Table::Roots => (
+            &["Root", "Lines", "Code", "Comments", "Blanks", "Files", "Commit"],
+            stats
+                .roots
+                .iter()
+                .map(|root| {
+                    vec![
+                        root.path.clone(),
+                        root.totals.lines.to_string(),
+                        root.totals.code.to_string(),
+                        root.totals.comments.to_string(),
+                        root.totals.blanks.to_string(),
+                        root.totals.files.to_string(),
+                        root.commit.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        ),
+    };
+
+    write_row(out, delimiter, header)?;
+    for row in &rows {
+        write_row(out, delimiter, row)?;
+    }
+    Ok(())
+}
+
+fn write_row<S: AsRef<str>>(
+    out: &mut dyn Write,
+    delimiter: char,
+    fields: &[S],
+) -> std::io::Result<()> {
+    let line = fields
+        .iter()
+        .map(|field| quote(field.as_ref(), delimiter))
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string());
+    writeln!(out, "{}", line)
+}
+
+fn quote(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,337 @@
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+use std::io::Write;
+
+use super::format_integer;
+use std::path::Path;
+
+use crate::{subdirectories, DirectoryInfo, Stats};
+
+/// Colours for chart slices and bars, in the same rusty palette as the TUI.
+const PALETTE: &[&str] = &[
+    "#b7410e", "#cd5c5c", "#8b4513", "#ffa07a", "#d2691e", "#a0522d", "#e9967a", "#800000",
+    "#f4a460", "#bc8f8f",
+];
+
+/// Number of slices in a chart before the remainder is grouped as "Others".
+const CHART_ITEMS: usize = 9;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2rem; color: #3b2416; background: #fffaf5; }
+h1 { color: #b7410e; }
+h2 { color: #8b4513; border-bottom: 2px solid #8b4513; padding-bottom: .25rem; }
+.meta { color: #7a5a48; }
+.charts { display: flex; flex-wrap: wrap; gap: 2rem; }
+.chart { flex: 1 1 24rem; }
+.legend { list-style: none; padding: 0; }
+.legend li { margin: .2rem 0; }
+.swatch { display: inline-block; width: .8rem; height: .8rem; margin-right: .4rem; vertical-align: middle; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2rem; }
+th, td { padding: .35rem .6rem; border-bottom: 1px solid #e6d5c8; }
+th { cursor: pointer; user-select: none; text-align: left; background: #f6e7dc; color: #b7410e; }
+th.num, td.num { text-align: right; }
+th[data-order="asc"]::after { content: " \25B2"; }
+th[data-order="desc"]::after { content: " \25BC"; }
+tr:hover td { background: #fbefe6; }
+.tree details, .tree .leaf { margin-left: 1.25rem; }
+.tree > details { margin-left: 0; }
+.tree summary { cursor: pointer; }
+.tree .leaf { padding-left: 1rem; }
+.dir { font-family: monospace; font-weight: bold; }
+.dim { color: #7a5a48; }
+"#;
+
+/// Sorts a table by the clicked column; numeric columns sort by the raw
+/// value stored in `data-value`.
+const SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable").forEach(function (table) {
+  table.querySelectorAll("th").forEach(function (th, column) {
+    th.addEventListener("click", function () {
+      var order = th.dataset.order === "desc" ? "asc" : "desc";
+      table.querySelectorAll("th").forEach(function (other) { delete other.dataset.order; });
+      th.dataset.order = order;
+      var body = table.tBodies[0];
+      var rows = Array.prototype.slice.call(body.rows);
+      rows.sort(function (a, b) {
+        var x = a.cells[column], y = b.cells[column];
+        var cmp = th.classList.contains("num")
+          ? Number(x.dataset.value) - Number(y.dataset.value)
+          : x.textContent.localeCompare(y.textContent);
+        return order === "asc" ? cmp : -cmp;
+      });
+      rows.forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});
+"#;
+
+/// Writes a single self-contained HTML page: no external stylesheets,
+/// scripts or fonts are referenced, so the file can be archived or opened
+/// offline.
+pub fn write(stats: &Stats, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let languages: Vec<_> = stats
+        .sorted_languages()
+        .into_iter()
+        .map(|(name, info)| {
+            (
+                name.as_str(),
+                [info.files, info.lines, info.code, info.comments, info.blanks],
+            )
+        })
+        .collect();
+    let contributors: Vec<_> = stats
+        .sorted_contributors()
+        .into_iter()
+        .map(|(name, info)| {
+            (
+                name.as_str(),
+                [info.files, info.lines, info.code, info.comments, info.blanks],
+            )
+        })
+        .collect();
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(html, "<title>RustyLines Report</title>")?;
+    writeln!(html, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(html, "<h1>RustyLines Report</h1>")?;
+    write!(html, "<p class=\"meta\">Scanned <code>{}</code>", escape(&stats.meta.root))?;
+    if let Some(commit) = &stats.meta.commit {
+        write!(html, " at commit <code>{}</code>", escape(commit))?;
+    }
+    let totals = stats.totals();
+    writeln!(
+        html,
+        ": {} lines in {} files.</p>",
+        format_integer(totals.lines),
+        format_integer(totals.files)
+    )?;
+
+    writeln!(html, "<div class=\"charts\">")?;
+    let language_lines: Vec<_> = languages.iter().map(|(name, v)| (*name, v[1])).collect();
+    let contributor_lines: Vec<_> = contributors.iter().map(|(name, v)| (*name, v[1])).collect();
+    write_pie_chart(&mut html, "Lines per Language", &language_lines)?;
+    write_bar_chart(&mut html, "Lines per Contributor", &contributor_lines)?;
+    writeln!(html, "</div>")?;
+
+    writeln!(html, "<h2>Languages</h2>")?;
+    write_table(&mut html, "Language", &languages)?;
+    writeln!(html, "<h2>Contributors</h2>")?;
+    write_table(&mut html, "Contributor", &contributors)?;
+
+    if let Some(root) = stats.directories.get(&stats.meta.root_key) {
+        writeln!(html, "<h2>Directories</h2>\n<div class=\"tree\">")?;
+        write_tree(&mut html, stats, &stats.meta.root_key, root, true)?;
+        writeln!(html, "</div>")?;
+    }
+
+    writeln!(html, "<script>{}</script>\n</body>\n</html>", SCRIPT)?;
+    out.write_all(html.as_bytes())?;
+    Ok(())
+}
+
+fn write_table(
+    html: &mut String,
+    name_header: &str,
+    rows: &[(&str, [usize; 5])],
+) -> std::fmt::Result {
+    writeln!(html, "<table class=\"sortable\">\n<thead><tr>")?;
+    write!(html, "<th>{}</th>", name_header)?;
+    for header in ["Files", "Lines", "Code", "Comments", "Blanks"] {
+        write!(html, "<th class=\"num\">{}</th>", header)?;
+    }
+    writeln!(html, "</tr></thead>\n<tbody>")?;
+    for (name, values) in rows {
+        write!(html, "<tr><td>{}</td>", escape(name))?;
+        for value in values {
+            write!(
+                html,
+                "<td class=\"num\" data-value=\"{}\">{}</td>",
+                value,
+                format_integer(*value)
+            )?;
+        }
+        writeln!(html, "</tr>")?;
+    }
+    writeln!(html, "</tbody>\n</table>")
+}
+
+/// Writes `dir` and everything below it as nested `<details>` elements,
+/// which browsers expand and collapse without any script.
+fn write_tree(
+    html: &mut String,
+    stats: &Stats,
+    path: &str,
+    info: &DirectoryInfo,
+    open: bool,
+) -> std::fmt::Result {
+    let name = match Path::new(path).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None if path.is_empty() => ".".to_string(),
+        None => path.to_string(),
+    };
+    let children = subdirectories(&stats.directories, path);
+    let top_language = info
+        .languages
+        .iter()
+        .max_by(|a, b| a.1.lines.cmp(&b.1.lines).then_with(|| b.0.cmp(a.0)))
+        .map_or("", |(name, _)| name.as_str());
+    let summary = format!(
+        "<span class=\"dir\">{}/</span> <span class=\"dim\">{} lines &middot; {} files &middot; mostly {}</span>",
+        escape(&name),
+        format_integer(info.lines),
+        format_integer(info.files),
+        escape(top_language)
+    );
+
+    if children.is_empty() {
+        return writeln!(html, "<div class=\"leaf\">{}</div>", summary);
+    }
+    writeln!(
+        html,
+        "<details{}><summary>{}</summary>",
+        if open { " open" } else { "" },
+        summary
+    )?;
+    for (child_path, child) in children {
+        write_tree(html, stats, child_path, child, false)?;
+    }
+    writeln!(html, "</details>")
+}
+
+/// Keeps the largest items and groups the remainder as "Others".
+fn chart_items<'a>(items: &[(&'a str, usize)]) -> Vec<(&'a str, usize)> {
+    let mut shown: Vec<_> = items.iter().take(CHART_ITEMS).copied().collect();
+    let others: usize = items.iter().skip(CHART_ITEMS).map(|(_, n)| n).sum();
+    if others > 0 {
+        shown.push(("Others", others));
+    }
+    shown
+}
+
+fn write_pie_chart(html: &mut String, title: &str, items: &[(&str, usize)]) -> std::fmt::Result {
+    let items = chart_items(items);
+    let total: usize = items.iter().map(|(_, n)| n).sum();
+
+    writeln!(html, "<div class=\"chart\">\n<h2>{}</h2>", title)?;
+    writeln!(
+        html,
+        "<svg viewBox=\"-110 -110 220 220\" width=\"260\" height=\"260\" role=\"img\" aria-label=\"{}\">",
+        title
+    )?;
+    let mut angle = -PI / 2.0;
+    for (i, (name, value)) in items.iter().enumerate() {
+        if *value == 0 {
+            continue;
+        }
+        let colour = PALETTE[i % PALETTE.len()];
+        let share = *value as f64 / total as f64;
+        if share >= 1.0 {
+            writeln!(
+                html,
+                "<circle r=\"100\" fill=\"{}\"><title>{}: {}</title></circle>",
+                colour,
+                escape(name),
+                format_integer(*value)
+            )?;
+            break;
+        }
+        let end = angle + share * 2.0 * PI;
+        writeln!(
+            html,
+            "<path d=\"M0,0 L{:.2},{:.2} A100,100 0 {} 1 {:.2},{:.2} Z\" fill=\"{}\"><title>{}: {}</title></path>",
+            100.0 * angle.cos(),
+            100.0 * angle.sin(),
+            if share > 0.5 { 1 } else { 0 },
+            100.0 * end.cos(),
+            100.0 * end.sin(),
+            colour,
+            escape(name),
+            format_integer(*value)
+        )?;
+        angle = end;
+    }
+    writeln!(html, "</svg>")?;
+
+    writeln!(html, "<ul class=\"legend\">")?;
+    for (i, (name, value)) in items.iter().enumerate() {
+        writeln!(
+            html,
+            "<li><span class=\"swatch\" style=\"background:{}\"></span>{} &ndash; {} ({:.1}%)</li>",
+            PALETTE[i % PALETTE.len()],
+            escape(name),
+            format_integer(*value),
+            *value as f64 * 100.0 / total.max(1) as f64
+        )?;
+    }
+    writeln!(html, "</ul>\n</div>")
+}
+
+fn write_bar_chart(html: &mut String, title: &str, items: &[(&str, usize)]) -> std::fmt::Result {
+    let items = chart_items(items);
+    let max = items.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    let bar_height = 22;
+    let height = items.len() * bar_height;
+
+    writeln!(html, "<div class=\"chart\">\n<h2>{}</h2>", title)?;
+    writeln!(
+        html,
+        "<svg viewBox=\"0 0 520 {}\" width=\"100%\" role=\"img\" aria-label=\"{}\">",
+        height.max(1),
+        title
+    )?;
+    for (i, (name, value)) in items.iter().enumerate() {
+        let y = i * bar_height;
+        let width = *value as f64 / max as f64 * 300.0;
+        writeln!(
+            html,
+            "<text x=\"150\" y=\"{}\" text-anchor=\"end\" font-size=\"12\">{}</text>",
+            y + 15,
+            escape(&truncate(name, 24))
+        )?;
+        writeln!(
+            html,
+            "<rect x=\"158\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"><title>{}: {}</title></rect>",
+            y + 3,
+            width,
+            bar_height - 6,
+            PALETTE[i % PALETTE.len()],
+            escape(name),
+            format_integer(*value)
+        )?;
+        writeln!(
+            html,
+            "<text x=\"{:.1}\" y=\"{}\" font-size=\"12\">{}</text>",
+            164.0 + width,
+            y + 15,
+            format_integer(*value)
+        )?;
+    }
+    writeln!(html, "</svg>\n</div>")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('\u{2026}');
+    truncated
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::languages::{self, Category};
+use crate::{
+    ContributorInfo, DirectoryInfo, FileInfo, LanguageInfo, Problem, RootInfo, ScanMeta,
+    SkippedFile, Stats,
+};
+
+/// Bumped whenever a field is removed or changes meaning. Adding fields is
+/// not a breaking change.
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Report<'a> {
+    schema_version: u32,
+    tool_version: &'static str,
+    meta: &'a ScanMeta,
+    totals: LanguageInfo,
+    roots: &'a [RootInfo],
+    categories: Vec<CategoryEntry<'a>>,
+    languages: Vec<LanguageEntry<'a>>,
+    contributors: Vec<Named<'a, ContributorInfo>>,
+    files: Vec<&'a FileInfo>,
+    directories: Vec<Located<'a, DirectoryInfo>>,
+    skipped: Vec<&'a SkippedFile>,
+    problems: Vec<&'a Problem>,
+}
+
+#[derive(Serialize)]
+struct Named<'a, T> {
+    name: &'a str,
+    #[serde(flatten)]
+    info: &'a T,
+}
+
+#[derive(Serialize)]
+struct LanguageEntry<'a> {
+    name: &'a str,
+    category: Category,
+    #[serde(flatten)]
+    info: &'a LanguageInfo,
+}
+
+#[derive(Serialize)]
+struct CategoryEntry<'a> {
+    name: Category,
+    #[serde(flatten)]
+    info: LanguageInfo,
+    languages: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Located<'a, T> {
+    path: &'a str,
+    #[serde(flatten)]
+    info: &'a T,
+}
+
+pub fn write(stats: &Stats, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let report = Report {
+        schema_version: SCHEMA_VERSION,
+        tool_version: env!("CARGO_PKG_VERSION"),
+        meta: &stats.meta,
+        totals: stats.totals(),
+        roots: &stats.roots,
+        categories: stats
+            .categories()
+            .into_iter()
+            .map(|group| CategoryEntry {
+                name: group.category,
+                info: group.totals,
+                languages: group.languages.iter().map(|(name, _)| name.as_str()).collect(),
+            })
+            .collect(),
+        languages: stats
+            .sorted_languages()
+            .into_iter()
+            .map(|(name, info)| LanguageEntry {
+                name,
+                category: languages::category_of(name),
+                info,
+            })
+            .collect(),
+        contributors: stats
+            .sorted_contributors()
+            .into_iter()
+            .map(|(name, info)| Named { name, info })
+            .collect(),
+        files: stats.sorted_files(),
+        directories: stats
+            .directories
+            .iter()
+            .map(|(path, info)| Located {
+                path: if path.is_empty() { "." } else { path },
+                info,
+            })
+            .collect(),
+        skipped: stats.sorted_skipped(),
+        problems: stats.report.sorted_problems(),
+    };
+
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)?;
+    Ok(())
+}
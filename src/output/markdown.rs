@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::io::Write;
+
+use super::format_integer;
+use crate::{LanguageInfo, Stats};
+
+#[derive(Default)]
+struct Row {
+    name: String,
+    lines: usize,
+    code: usize,
+    comments: usize,
+    blanks: usize,
+    files: usize,
+}
+
+impl Row {
+    fn new(name: String, info: &LanguageInfo) -> Row {
+        Row {
+            name,
+            lines: info.lines,
+            code: info.code,
+            comments: info.comments,
+            blanks: info.blanks,
+            files: info.files,
+        }
+    }
+
+    fn add(&mut self, other: &Row) {
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.files += other.files;
+    }
+}
+
+/// Writes a GitHub-flavoured Markdown report with a category summary, a
+/// language table per category and a contributor table, preceded by a table
+/// per scanned path when there are several. With `top`, each
+/// table keeps its first `top` rows and folds the rest into a single
+/// "Others" row so the totals still add up.
+pub fn write(stats: &Stats, top: Option<usize>, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "# RustyLines Report")?;
+    writeln!(out)?;
+    let scanned = if stats.roots.len() > 1 {
+        let roots: Vec<String> = stats
+            .roots
+            .iter()
+            .map(|root| format!("`{}`", root.path))
+            .collect();
+        roots.join(", ")
+    } else {
+        format!("`{}`", stats.meta.root)
+    };
+    match &stats.meta.commit {
+        Some(commit) => writeln!(
+            out,
+            "Scanned {} at commit `{}`.",
+            scanned,
+            &commit[..commit.len().min(12)]
+        )?,
+        None => writeln!(out, "Scanned {}.", scanned)?,
+    }
+
+    if stats.roots.len() > 1 {
+        let roots = stats
+            .roots
+            .iter()
+            .map(|root| Row::new(root.path.clone(), &root.totals))
+            .collect();
+        writeln!(out)?;
+        writeln!(out, "## Roots")?;
+        writeln!(out)?;
+        write_table(out, "Root", roots, None)?;
+    }
+
+    let groups = stats.categories();
+    let categories = groups
+        .iter()
+        .map(|group| Row::new(group.category.to_string(), &group.totals))
+        .collect();
+    let contributors = stats
+        .sorted_contributors()
+        .into_iter()
+        .map(|(name, info)| Row {
+            name: name.clone(),
+            lines: info.lines,
+            code: info.code,
+            comments: info.comments,
+            blanks: info.blanks,
+            files: info.files,
+        })
+        .collect();
+
+    writeln!(out)?;
+    writeln!(out, "## Categories")?;
+    writeln!(out)?;
+    write_table(out, "Category", categories, None)?;
+
+    writeln!(out)?;
+    writeln!(out, "## Languages")?;
+    for group in &groups {
+        let languages = group
+            .languages
+            .iter()
+            .map(|(name, info)| Row::new(name.to_string(), info))
+            .collect();
+        writeln!(out)?;
+        writeln!(out, "### {}", group.category)?;
+        writeln!(out)?;
+        write_table(out, "Language", languages, top)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "## Contributors")?;
+    writeln!(out)?;
+    write_table(out, "Contributor", contributors, top)?;
+
+    let skipped = stats.skipped_counts();
+    if !skipped.is_empty() {
+        let summary: Vec<String> = skipped
+            .iter()
+            .map(|(reason, files)| format!("{} {}", format_integer(*files), reason))
+            .collect();
+        writeln!(out)?;
+        writeln!(out, "Not counted: {} files.", summary.join(", "))?;
+    }
+
+    if !stats.report.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "## Problems")?;
+        writeln!(out)?;
+        for problem in stats.report.sorted_problems() {
+            let path = if problem.path.is_empty() { "." } else { &problem.path };
+            writeln!(out, "- `{}` ({}): {}", path, problem.kind, problem.message)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_table(
+    out: &mut dyn Write,
+    name_header: &str,
+    mut rows: Vec<Row>,
+    top: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let mut total = Row::default();
+    for row in &rows {
+        total.add(row);
+    }
+
+    if let Some(top) = top.filter(|&top| top < rows.len()) {
+        let rest = rows.split_off(top);
+        let mut others = Row {
+            name: format!("Others ({})", rest.len()),
+            ..Row::default()
+        };
+        for row in &rest {
+            others.add(row);
+        }
+        rows.push(others);
+    }
+
+    writeln!(
+        out,
+        "| {} | Files | Lines | Code | Comments | Blanks | % of Lines |",
+        name_header
+    )?;
+    writeln!(out, "| :--- | ---: | ---: | ---: | ---: | ---: | ---: |")?;
+    for row in &rows {
+        write_row(out, &escape(&row.name), row, total.lines)?;
+    }
+    write_row(out, "**Total**", &total, total.lines)?;
+    Ok(())
+}
+
+fn write_row(
+    out: &mut dyn Write,
+    name: &str,
+    row: &Row,
+    total_lines: usize,
+) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "| {} | {} | {} | {} | {} | {} | {} |",
+        name,
+        format_integer(row.files),
+        format_integer(row.lines),
+        format_integer(row.code),
+        format_integer(row.comments),
+        format_integer(row.blanks),
+        percentage(row.lines, total_lines),
+    )
+}
+
+fn percentage(part: usize, whole: usize) -> String {
+    if whole == 0 {
+        return "0.0%".to_string();
+    }
+    format!("{:.1}%", part as f64 * 100.0 / whole as f64)
+}
+
+/// Escapes characters that would break a table cell or be read as markup.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '|' | '\\' | '`' | '*' | '_' | '<' | '>' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
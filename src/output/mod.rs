@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::Stats;
+
+mod delimited;
+mod html;
+mod json;
+mod markdown;
+
+/// How scan results are presented. Everything except `Tui` is written
+/// headlessly and can be piped into other tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Tui,
+    Json,
+    Csv,
+    Tsv,
+    Markdown,
+    Html,
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &["tui", "json", "csv", "tsv", "markdown", "html"];
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tui" => Ok(OutputFormat::Tui),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(format!("Unknown output format '{}'", s)),
+        }
+    }
+}
+
+/// Which table the row-based formats (CSV and TSV) write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Table {
+    #[default]
+    Languages,
+    Contributors,
+    Files,
+    Roots,
+}
+
+impl Table {
+    pub const NAMES: &'static [&'static str] = &["languages", "contributors", "files", "roots"];
+}
+
+impl FromStr for Table {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "languages" => Ok(Table::Languages),
+            "contributors" => Ok(Table::Contributors),
+            "files" => Ok(Table::Files),
+            "roots" => Ok(Table::Roots),
+            _ => Err(format!("Unknown table '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    pub table: Table,
+    /// Maximum number of rows per table in summary reports.
+    pub top: Option<usize>,
+}
+
+pub fn write(
+    format: OutputFormat,
+    stats: &Stats,
+    options: &OutputOptions,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Tui => Err("The TUI cannot be written to a stream".into()),
+        OutputFormat::Json => json::write(stats, out),
+        OutputFormat::Csv => delimited::write(stats, options.table, ',', out),
+        OutputFormat::Tsv => delimited::write(stats, options.table, '\t', out),
+        OutputFormat::Markdown => markdown::write(stats, options.top, out),
+        OutputFormat::Html => html::write(stats, out),
+    }
+}
+
+/// Formats `n` with thousands separators, e.g. `1234567` as `1,234,567`.
+pub fn format_integer(n: usize) -> String {
+    let s = n.to_string();
+    let bytes = s.as_bytes();
+    let len = bytes.len();
+    let mut result = String::new();
+
+    for (i, &b) in bytes.iter().enumerate() {
+        result.push(b as char);
+        if (len - i - 1).is_multiple_of(3) && i != len - 1 {
+            result.push(',');
+        }
+    }
+    result
+}
+
+/// Formats seconds since the Unix epoch as a UTC date, e.g. `2024-03-09`.
+pub fn format_date(seconds: i64) -> String {
+    // Days to a civil date in the proleptic Gregorian calendar, counting
+    // from 0000-03-01 so that leap days fall at the end of each year.
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(86_399), "1970-01-01");
+        assert_eq!(format_date(86_400), "1970-01-02");
+        assert_eq!(format_date(1_710_028_799), "2024-03-09");
+        assert_eq!(format_date(4_102_444_800), "2100-01-01");
+    }
+
+    #[test]
+    fn leap_days() {
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(951_868_800), "2000-03-01");
+    }
+
+    #[test]
+    fn dates_before_the_epoch() {
+        assert_eq!(format_date(-1), "1969-12-31");
+        assert_eq!(format_date(-31_536_000), "1969-01-01");
+    }
+}
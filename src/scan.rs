@@ -0,0 +1,747 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use git2::Oid;
+use ignore::{DirEntry, ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState};
+
+use crate::counter::{self, LineCounts};
+use crate::encoding::{self, Decoded};
+use crate::filter::PathFilter;
+use crate::git::{GitContext, GitError};
+use crate::heuristics::Siblings;
+use crate::identity;
+use crate::languages::Detection;
+use crate::skip::{self, SkipReason};
+use crate::{
+    languages, Authorship, ContributorInfo, DirectoryInfo, FileInfo, Problem, ProblemKind, RootInfo,
+    ScanMeta, ScanOptions, SkippedFile, Stats,
+};
+
+/// Name of the RustyLines-specific ignore file, read with the same syntax as
+/// `.gitignore`.
+const IGNORE_FILENAME: &str = ".rustylinesignore";
+
+/// How much of an unrecognised file is read to look for a shebang or modeline.
+const DETECTION_HEAD_BYTES: u64 = 1024;
+
+/// How much of the end of a larger unrecognised file is read to look for a
+/// vim modeline, which may be on any of its last lines.
+const DETECTION_TAIL_BYTES: u64 = 512;
+
+type ScanError = Box<dyn Error + Send + Sync>;
+
+/// Scans every path in `paths`, directories and single files alike, and
+/// merges the results into one `Stats` with a `RootInfo` per path. A path
+/// inside another one is only scanned once, as part of the outer path.
+///
+/// Files and directories that cannot be read, and git errors, are recorded in
+/// `Stats::report` and the scan carries on, unless `options.strict` is set.
+pub fn process_paths(paths: &[&Path], options: &ScanOptions) -> Result<Stats, Box<dyn Error>> {
+    let roots = distinct_roots(paths)?;
+    let tree_root = common_directory(&roots);
+    let root_key = display_path(&tree_root);
+
+    let mut stats = Stats::default();
+    let mut commits = Vec::new();
+    for root in &roots {
+        let (root_stats, commit) = process_root(root, &root_key, options)?;
+        let path = display_path(root);
+        stats.roots.push(RootInfo {
+            path: if path.is_empty() { ".".to_string() } else { path },
+            commit: commit.clone(),
+            totals: root_stats.totals(),
+        });
+        stats.merge(root_stats);
+        commits.push(commit);
+    }
+    stats.shorten_contributor_names();
+
+    commits.dedup();
+    stats.meta = ScanMeta {
+        root: match paths {
+            [path] if path.is_dir() => path.display().to_string(),
+            _ if root_key.is_empty() => ".".to_string(),
+            _ => root_key.clone(),
+        },
+        commit: match commits.as_slice() {
+            [commit] => commit.clone(),
+            _ => None,
+        },
+        scanned_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        root_key,
+    };
+    Ok(stats)
+}
+
+/// Checks that every path exists and drops those inside another path. Roots
+/// are rebuilt from their components, so `src/` and `src/./` become `src`
+/// like the ancestors of the files below them. A bare file name gets a `./`
+/// prefix so that its directory is `.`.
+fn distinct_roots(paths: &[&Path]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let any_absolute = paths.iter().any(|path| path.is_absolute());
+    let mut roots: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for path in paths {
+        if !path.is_dir() && !path.is_file() {
+            return Err(format!("The path '{}' is not a file or directory.", path.display()).into());
+        }
+        let canonical = path.canonicalize()?;
+        let mut root: PathBuf = if any_absolute {
+            std::path::absolute(path)?.components().collect()
+        } else {
+            path.components().collect()
+        };
+        if root.parent() == Some(Path::new("")) && root.is_file() {
+            root = Path::new(".").join(root);
+        }
+        roots.retain(|(other, _)| !other.starts_with(&canonical));
+        if !roots.iter().any(|(other, _)| canonical.starts_with(other)) {
+            roots.push((canonical, root));
+        }
+    }
+    Ok(roots.into_iter().map(|(_, root)| root).collect())
+}
+
+/// The deepest directory containing every root, which becomes the top of the
+/// directory tree. For a single directory that is the directory itself.
+fn common_directory(roots: &[PathBuf]) -> PathBuf {
+    let directory = |root: &PathBuf| {
+        let root = root.strip_prefix(".").unwrap_or(root);
+        match root.parent() {
+            Some(parent) if !root.is_dir() => parent.to_path_buf(),
+            _ => root.to_path_buf(),
+        }
+    };
+    let mut common = roots.first().map(directory).unwrap_or_default();
+    for root in roots.iter().skip(1) {
+        let dir = directory(root);
+        while !dir.starts_with(&common) && common.pop() {}
+    }
+    common
+}
+
+/// Walks `root` on a pool of worker threads. Every worker accumulates into its
+/// own `Stats`, which are merged once the worker finishes, so the totals do
+/// not depend on the number of threads or the order files are visited in.
+/// Directory rollups go up to the directory keyed `root_key`. Returns the
+/// commit blamed at along with the results.
+fn process_root(
+    root: &Path,
+    root_key: &str,
+    options: &ScanOptions,
+) -> Result<(Stats, Option<String>), Box<dyn Error>> {
+    let mut context = ScanContext {
+        root,
+        root_key: root_key.to_string(),
+        options,
+        siblings: Siblings::disk(),
+    };
+    let mut stats = Stats::default();
+    let git_root = match root.parent() {
+        Some(parent) if !root.is_dir() => parent,
+        _ => root,
+    };
+    let rev = options.rev.as_deref();
+    let git = match GitContext::open(git_root, rev, &options.ignore_revs) {
+        Ok(git) => Some(git),
+        Err(e) if rev.is_some() => {
+            return Err(format!("Cannot read {} at {}: {}", root.display(), rev.unwrap(), e).into())
+        }
+        Err(e @ GitError::IgnoreRev(_)) => {
+            return Err(format!("Cannot blame {}: {}", root.display(), e).into())
+        }
+        Err(e) if e.is_not_found() => None,
+        Err(e) => {
+            context.report(&mut stats, Problem::new(git_root, ProblemKind::Git, &e))?;
+            None
+        }
+    };
+
+    let results = Mutex::new(stats);
+    let error = Mutex::new(None);
+    match &git {
+        Some(git) if git.reads_tree() => process_tree(git, &mut context, &results, &error)?,
+        _ => build_walker(root, options)?
+            .build_parallel()
+            .visit(&mut ScanVisitorBuilder {
+                context: &context,
+                git: git.as_ref(),
+                results: &results,
+                error: &error,
+            }),
+    }
+
+    if let Some(e) = error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    let commit = git.as_ref().and_then(GitContext::commit_id).map(|oid| oid.to_string());
+    Ok((results.into_inner().unwrap(), commit))
+}
+
+/// Scans the files below the root in the tree of the commit given with
+/// `--rev`, or of `HEAD` in a bare repository, instead of the working
+/// directory, so local edits and untracked files make no difference. Blobs
+/// are read straight from the object database, so no checkout is needed.
+/// The same filters apply as to a walk, except for ignore files: everything
+/// in the tree is tracked. Like the walk, the files are spread over a pool
+/// of threads, each with its own `Stats`.
+fn process_tree(
+    git: &GitContext,
+    context: &mut ScanContext,
+    results: &Mutex<Stats>,
+    error: &Mutex<Option<ScanError>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = context.options;
+    let root = context.root;
+    let filter = PathFilter::new(root, &options.include, &options.exclude)?;
+    let mut listed = Vec::new();
+    let files = git.tree_files(root, &mut |path, is_dir| {
+        if !is_dir {
+            listed.push(path.to_path_buf());
+        }
+        let depth = path.strip_prefix(root).map_or(0, |p| p.components().count());
+        let within_depth = match options.max_depth {
+            Some(max) if is_dir => depth < max,
+            Some(max) => depth <= max,
+            None => true,
+        };
+        within_depth && filter.allows(path, is_dir)
+    })?;
+    context.siblings = Siblings::tree(listed.iter().map(PathBuf::as_path));
+    let context = &*context;
+
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    // `GitContext` cannot be shared between threads, so every worker gets
+    // its own handle.
+    let handles = (0..threads.min(files.len()))
+        .map(|_| git.try_clone())
+        .collect::<Result<Vec<_>, _>>()?;
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for git in handles {
+            let (files, next) = (&files, &next);
+            scope.spawn(move || {
+                let mut stats = Stats::default();
+                while let Some(file) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if error.lock().unwrap().is_some() {
+                        break;
+                    }
+                    let origin = Origin::Tree(&git, file.blob);
+                    let Err(e) = process_file(&file.path, origin, &mut stats, Some(&git), context)
+                    else {
+                        continue;
+                    };
+                    let problem = Problem::new(&file.path, error_kind(&*e), &e);
+                    if let Err(e) = context.report(&mut stats, problem) {
+                        error.lock().unwrap().get_or_insert(e.into());
+                    }
+                }
+                results.lock().unwrap().merge(stats);
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Builds a recursive walker over `dir` that skips everything excluded by
+/// `.gitignore`, `.ignore`, `.rustylinesignore`, `.git/info/exclude` and the
+/// global `core.excludesFile`, unless `--no-ignore` was given, as well as
+/// anything the `--include` and `--exclude` globs leave out and anything
+/// deeper than `--max-depth`. Excluded directories are never descended into.
+fn build_walker(dir: &Path, options: &ScanOptions) -> Result<WalkBuilder, String> {
+    let filter = PathFilter::new(dir, &options.include, &options.exclude)?;
+    let respect_ignores = !options.no_ignore;
+    let mut builder = WalkBuilder::new(dir);
+    builder
+        .hidden(false)
+        .parents(respect_ignores)
+        .ignore(respect_ignores)
+        .git_ignore(respect_ignores)
+        .git_global(respect_ignores)
+        .git_exclude(respect_ignores)
+        .require_git(false)
+        .threads(options.threads)
+        .max_depth(options.max_depth)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            entry.file_name() != ".git" && filter.allows(entry.path(), is_dir)
+        });
+    if respect_ignores {
+        builder.add_custom_ignore_filename(IGNORE_FILENAME);
+    }
+    Ok(builder)
+}
+
+/// What every worker needs to know about the scan.
+struct ScanContext<'s> {
+    root: &'s Path,
+    root_key: String,
+    options: &'s ScanOptions,
+    /// The files next to each other below `root`, for the heuristics.
+    siblings: Siblings,
+}
+
+impl ScanContext<'_> {
+    /// Records `problem` in `stats`, or returns it as an error in strict mode.
+    fn report(&self, stats: &mut Stats, problem: Problem) -> Result<(), String> {
+        if self.options.strict {
+            return Err(problem.to_string());
+        }
+        stats.report.problems.push(problem);
+        Ok(())
+    }
+}
+
+struct ScanVisitorBuilder<'s> {
+    context: &'s ScanContext<'s>,
+    git: Option<&'s GitContext>,
+    results: &'s Mutex<Stats>,
+    error: &'s Mutex<Option<ScanError>>,
+}
+
+impl<'s> ParallelVisitorBuilder<'s> for ScanVisitorBuilder<'s> {
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        let mut stats = Stats::default();
+        // A worker without its own handle credits every file it scans to an
+        // unknown contributor, which is a problem in itself.
+        let git = match self.git.map(GitContext::try_clone) {
+            Some(Ok(git)) => Some(git),
+            Some(Err(e)) => {
+                let problem = Problem::new(self.context.root, ProblemKind::Git, &e);
+                if let Err(e) = self.context.report(&mut stats, problem) {
+                    self.error.lock().unwrap().get_or_insert(e.into());
+                }
+                None
+            }
+            None => None,
+        };
+        Box::new(ScanVisitor {
+            context: self.context,
+            git,
+            stats,
+            results: self.results,
+            error: self.error,
+        })
+    }
+}
+
+/// Per-thread visitor. Its `Stats` are merged into the shared results when
+/// the walker drops it at the end of the scan.
+struct ScanVisitor<'s> {
+    context: &'s ScanContext<'s>,
+    git: Option<GitContext>,
+    stats: Stats,
+    results: &'s Mutex<Stats>,
+    error: &'s Mutex<Option<ScanError>>,
+}
+
+impl ScanVisitor<'_> {
+    fn visit_entry(&mut self, entry: &DirEntry) -> Result<(), ScanError> {
+        let path = entry.path();
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            process_file(path, Origin::Disk, &mut self.stats, self.git.as_ref(), self.context)?;
+        }
+        Ok(())
+    }
+}
+
+impl ParallelVisitor for ScanVisitor<'_> {
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        if self.error.lock().unwrap().is_some() {
+            return WalkState::Quit;
+        }
+        let problem = match entry {
+            Ok(entry) => match self.visit_entry(&entry) {
+                Ok(()) => return WalkState::Continue,
+                Err(e) => Problem::new(entry.path(), error_kind(&*e), &e),
+            },
+            Err(e) => walk_problem(&e),
+        };
+        match self.context.report(&mut self.stats, problem) {
+            Ok(()) => WalkState::Continue,
+            Err(e) => {
+                self.error.lock().unwrap().get_or_insert(e.into());
+                WalkState::Quit
+            }
+        }
+    }
+}
+
+impl Drop for ScanVisitor<'_> {
+    fn drop(&mut self) {
+        let stats = std::mem::take(&mut self.stats);
+        if let Ok(mut results) = self.results.lock() {
+            results.merge(stats);
+        }
+    }
+}
+
+fn process_file(
+    path: &Path,
+    origin: Origin,
+    stats: &mut Stats,
+    git: Option<&GitContext>,
+    context: &ScanContext,
+) -> Result<(), ScanError> {
+    let options = context.options;
+    let relative = path.strip_prefix(context.root).unwrap_or(path);
+    let skipped_by_path = if skip::is_vendored(relative) {
+        Some(SkipReason::Vendored)
+    } else {
+        skip::check_name(path)
+    };
+    if let Some(reason) = skipped_by_path.filter(|&reason| !options.includes_skipped(reason)) {
+        if languages::detect_by_filename(path).is_some()
+            || languages::detect_by_extension(path).is_some()
+        {
+            let bytes = origin.size(path)?;
+            record_skipped(stats, path, reason, bytes);
+        }
+        return Ok(());
+    }
+
+    if let Some((detection, source)) = read_source(path, origin, context)? {
+        let language = detection.language;
+        if !options.includes_language(language) {
+            return Ok(());
+        }
+        if let Some(reason) = skip::check_content(&source.content, language) {
+            if !options.includes_skipped(reason) {
+                record_skipped(stats, path, reason, source.size);
+                return Ok(());
+            }
+        }
+
+        let kinds = counter::classify(&source.content, language);
+        let counts = counter::count(&kinds);
+
+        let lang_info = stats.languages.entry(language.name.to_string()).or_default();
+        lang_info.add_counts(&counts);
+        lang_info.files += 1;
+
+        let mut file_contributors: BTreeMap<String, (LineCounts, Authorship)> = BTreeMap::new();
+        let working = matches!(origin, Origin::Disk).then_some(&source);
+        match git.map(|git| git.get_file_info(path, working, kinds.len())) {
+            Some(Ok(hunks)) => {
+                for hunk in hunks {
+                    let contributor = options.identities.contributor(&hunk.name, &hunk.email);
+                    let (counts, authorship) = file_contributors.entry(contributor).or_default();
+                    authorship.add(&hunk);
+                    let end = (hunk.start + hunk.lines).min(kinds.len());
+                    for &kind in kinds.get(hunk.start..end).unwrap_or_default() {
+                        counts.add(kind);
+                    }
+                }
+            }
+            Some(Err(e)) if context.options.strict => return Err(e.into()),
+            Some(Err(e)) => {
+                stats.report.problems.push(Problem::new(path, ProblemKind::Git, &e));
+                let unknown = (counts, Authorship::default());
+                file_contributors.insert(identity::UNKNOWN.to_string(), unknown);
+            }
+            None => {
+                let unknown = (counts, Authorship::default());
+                file_contributors.insert(identity::UNKNOWN.to_string(), unknown);
+            }
+        }
+        for (contributor, (counts, authorship)) in &file_contributors {
+            let contrib_info = stats.contributors.entry(contributor.clone()).or_default();
+            update_contributor_info(contrib_info, counts, authorship);
+        }
+
+        let file = FileInfo {
+            path: display_path(path),
+            language: language.name.to_string(),
+            detected_by: detection.rule,
+            heuristic: detection.heuristic,
+            lines: counts.lines(),
+            code: counts.code,
+            comments: counts.comments,
+            blanks: counts.blanks,
+            encoding: source.encoding.name(),
+            bytes: source.size,
+            contributors: file_contributors
+                .iter()
+                .map(|(name, (counts, _))| (name.clone(), counts.lines()))
+                .collect(),
+        };
+        add_to_directories(
+            &mut stats.directories,
+            &file,
+            &counts,
+            &file_contributors,
+            &context.root_key,
+        );
+        stats.files.push(file);
+    }
+    Ok(())
+}
+
+/// Adds `file` to the rollup of every directory from its parent up to and
+/// including the scan root.
+fn add_to_directories(
+    directories: &mut BTreeMap<String, DirectoryInfo>,
+    file: &FileInfo,
+    counts: &LineCounts,
+    contributors: &BTreeMap<String, (LineCounts, Authorship)>,
+    root_key: &str,
+) {
+    for dir in Path::new(&file.path).ancestors().skip(1) {
+        let key = dir.display().to_string();
+        let info = directories.entry(key.clone()).or_default();
+        info.lines += counts.lines();
+        info.code += counts.code;
+        info.comments += counts.comments;
+        info.blanks += counts.blanks;
+        info.files += 1;
+
+        let lang_info = info.languages.entry(file.language.clone()).or_default();
+        lang_info.add_counts(counts);
+        lang_info.files += 1;
+
+        for (contributor, (counts, authorship)) in contributors {
+            let contrib_info = info.contributors.entry(contributor.clone()).or_default();
+            update_contributor_info(contrib_info, counts, authorship);
+        }
+
+        if key == root_key {
+            break;
+        }
+    }
+}
+
+/// The path as walked, without the `./` a relative root like `.` adds.
+fn display_path(path: &Path) -> String {
+    path.strip_prefix(".").unwrap_or(path).display().to_string()
+}
+
+/// Adds one file's worth of a contributor's lines to `contrib_info`.
+fn update_contributor_info(
+    contrib_info: &mut ContributorInfo,
+    counts: &LineCounts,
+    authorship: &Authorship,
+) {
+    contrib_info.add_counts(counts);
+    contrib_info.files += 1;
+    contrib_info.authorship.merge(authorship);
+}
+
+impl Problem {
+    fn new(path: &Path, kind: ProblemKind, error: &dyn std::fmt::Display) -> Problem {
+        Problem {
+            path: display_path(path),
+            kind,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Classifies an error returned while processing a file.
+fn error_kind(error: &(dyn Error + 'static)) -> ProblemKind {
+    if let Some(e) = error.downcast_ref::<io::Error>() {
+        ProblemKind::of_io(e)
+    } else if error.is::<GitError>() {
+        ProblemKind::Git
+    } else {
+        ProblemKind::Io
+    }
+}
+
+/// Turns an error reported by the walker into a `Problem`, taking the path
+/// out of the message.
+fn walk_problem(error: &ignore::Error) -> Problem {
+    match error {
+        ignore::Error::WithPath { path, err } => Problem {
+            path: display_path(path),
+            ..walk_problem(err)
+        },
+        ignore::Error::WithDepth { err, .. } => walk_problem(err),
+        ignore::Error::Loop { child, .. } => Problem::new(child, ProblemKind::Walk, error),
+        _ => Problem {
+            path: String::new(),
+            kind: error.io_error().map_or(ProblemKind::Walk, ProblemKind::of_io),
+            message: error.to_string(),
+        },
+    }
+}
+
+fn record_skipped(stats: &mut Stats, path: &Path, reason: SkipReason, bytes: u64) {
+    stats.skipped.push(SkippedFile {
+        path: display_path(path),
+        reason,
+        bytes,
+    });
+}
+
+/// Where the content of a file comes from.
+#[derive(Clone, Copy)]
+enum Origin<'g> {
+    /// The working directory.
+    Disk,
+    /// A blob in the tree of the commit being blamed, see `process_tree`.
+    Tree(&'g GitContext, Oid),
+}
+
+impl Origin<'_> {
+    fn read(self, path: &Path) -> Result<Vec<u8>, ScanError> {
+        match self {
+            Origin::Disk => Ok(fs::read(path)?),
+            Origin::Tree(git, blob) => Ok(git.read_blob(blob)?),
+        }
+    }
+
+    fn size(self, path: &Path) -> Result<u64, ScanError> {
+        match self {
+            Origin::Disk => Ok(fs::metadata(path)?.len()),
+            Origin::Tree(git, blob) => Ok(git.blob_size(blob)?),
+        }
+    }
+}
+
+/// Reads `path` if its language can be detected. Of files on disk that are
+/// not recognised by name or extension, only the beginning and end are read
+/// to look for a shebang or modeline, so large unrelated files are never read
+/// in full.
+fn read_source(
+    path: &Path,
+    origin: Origin,
+    context: &ScanContext,
+) -> Result<Option<(Detection, Decoded)>, ScanError> {
+    let (options, siblings) = (context.options, &context.siblings);
+    let known = languages::detect_by_filename(path).is_some()
+        || languages::detect_by_extension(path).is_some();
+    if !known && matches!(origin, Origin::Disk) {
+        let sample = read_detection_sample(path, options)?;
+        if languages::detect(path, &sample, siblings).is_none() {
+            return Ok(None);
+        }
+    }
+
+    let source = encoding::decode(origin.read(path)?, options.encoding);
+    Ok(languages::detect(path, &source.content, siblings).map(|detection| (detection, source)))
+}
+
+/// The decoded beginning of `path` and, for a larger file, its last few lines,
+/// separated by a line break.
+fn read_detection_sample(path: &Path, options: &ScanOptions) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if size <= DETECTION_HEAD_BYTES + DETECTION_TAIL_BYTES {
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        return Ok(encoding::decode(content, options.encoding).content);
+    }
+
+    let mut head = Vec::new();
+    (&mut file).take(DETECTION_HEAD_BYTES).read_to_end(&mut head)?;
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::End(-(DETECTION_TAIL_BYTES as i64)))?;
+    file.read_to_end(&mut tail)?;
+
+    let head = encoding::decode(head, options.encoding);
+    let tail = encoding::decode(tail, Some(head.encoding)).content;
+    // The first line of the tail is most likely cut off.
+    let last_lines = tail.iter().position(|&b| b == b'\n').map_or(&tail[..0], |i| &tail[i + 1..]);
+    let mut sample = head.content;
+    sample.push(b'\n');
+    sample.extend_from_slice(last_lines);
+    Ok(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root_keys(paths: &[&str]) -> (Vec<String>, String) {
+        let paths: Vec<&Path> = paths.iter().map(Path::new).collect();
+        let roots = distinct_roots(&paths).unwrap();
+        let keys = roots.iter().map(|root| display_path(root)).collect();
+        (keys, display_path(&common_directory(&roots)))
+    }
+
+    #[test]
+    fn trailing_separators() {
+        let expected = (vec!["src".to_string()], "src".to_string());
+        assert_eq!(root_keys(&["src"]), expected);
+        assert_eq!(root_keys(&["src/"]), expected);
+        assert_eq!(root_keys(&["src/./"]), expected);
+        let absolute = std::path::absolute("src").unwrap().display().to_string();
+        let expected = (vec![absolute.clone()], absolute);
+        assert_eq!(root_keys(&[&format!("{}/", expected.1)]), expected);
+    }
+
+    #[test]
+    fn nested_roots() {
+        let expected = (vec!["src".to_string()], "src".to_string());
+        assert_eq!(root_keys(&["src", "src/output"]), expected);
+        assert_eq!(root_keys(&["src/output", "src"]), expected);
+        assert_eq!(root_keys(&["src/main.rs", "src"]), expected);
+        let (keys, common) = root_keys(&["src/output", "src/main.rs"]);
+        assert_eq!(keys, ["src/output", "src/main.rs"]);
+        assert_eq!(common, "src");
+    }
+
+    #[test]
+    fn bare_file_names() {
+        let roots = distinct_roots(&[Path::new("build.rs")]).unwrap();
+        assert_eq!(roots, [PathBuf::from("./build.rs")]);
+        assert_eq!(common_directory(&roots), PathBuf::new());
+        let expected = (vec!["build.rs".to_string()], String::new());
+        assert_eq!(root_keys(&["build.rs"]), expected);
+        assert_eq!(root_keys(&["./build.rs"]), expected);
+    }
+
+    #[test]
+    fn absolute_and_relative_roots() {
+        let absolute = |path| std::path::absolute(path).unwrap().display().to_string();
+        let build = absolute("build.rs");
+        let expected = (vec![absolute("src"), build.clone()], absolute("."));
+        assert_eq!(root_keys(&["src", &build]), expected);
+        // Relative roots inside an absolute one are still dropped.
+        let (keys, _) = root_keys(&[&absolute("src"), "src/output"]);
+        assert_eq!(keys, [absolute("src")]);
+    }
+
+    #[test]
+    fn modelines_at_the_end_of_unrecognised_files() {
+        let dir = std::env::temp_dir().join(format!("rustylines-modeline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let body = "print('a line of python long enough to matter')\n".repeat(50);
+        fs::write(dir.join("long"), format!("{}# vim: set ft=python:\n", body)).unwrap();
+        fs::write(dir.join("short"), "print('x')\n# vim: set ft=python:\n").unwrap();
+        fs::write(dir.join("middle"), format!("{}# vim: set ft=python:\n{}", body, body)).unwrap();
+        let stats = process_paths(&[&dir], &ScanOptions::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut files: Vec<(String, String)> = stats
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|file| (file.path, file.language))
+            .collect();
+        files.sort();
+        let path = |name: &str| dir.join(name).display().to_string();
+        assert_eq!(
+            files,
+            [(path("long"), "Python".to_string()), (path("short"), "Python".to_string())]
+        );
+    }
+
+    #[test]
+    fn missing_roots() {
+        let error = distinct_roots(&[Path::new("no/such/dir")]).unwrap_err();
+        assert_eq!(error.to_string(), "The path 'no/such/dir' is not a file or directory.");
+    }
+}
@@ -0,0 +1,200 @@
+use crate::counter::{self, LineKind};
+use crate::languages::Language;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Component, Path};
+
+/// How much of a file is searched for NUL bytes, as git does.
+const BINARY_CHECK_BYTES: usize = 8000;
+
+/// Number of leading lines searched for a generated-code marker.
+const GENERATED_MARKER_LINES: usize = 5;
+
+/// Files at least this large whose lines are longer than this on average are
+/// considered minified.
+const MINIFIED_MIN_BYTES: usize = 1024;
+const MINIFIED_AVERAGE_LINE: usize = 500;
+
+/// Directory names that hold third-party code.
+const VENDORED_DIRECTORIES: &[&str] = &[
+    "vendor",
+    "vendors",
+    "third_party",
+    "third-party",
+    "thirdparty",
+    "3rdparty",
+    "node_modules",
+    "bower_components",
+    "jspm_packages",
+    "Pods",
+    "Carthage",
+];
+
+/// Lockfiles and other files written by tools rather than people.
+const GENERATED_FILENAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "Podfile.lock",
+    "Package.resolved",
+    "poetry.lock",
+    "Pipfile.lock",
+    "pdm.lock",
+    "uv.lock",
+    "go.sum",
+    "flake.lock",
+    "mix.lock",
+    "pubspec.lock",
+    "packages.lock.json",
+    "gradle.lockfile",
+    "Manifest.toml",
+];
+
+/// File name endings of common code generators' output.
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".pb.go",
+    ".pb.gw.go",
+    ".pb.cc",
+    ".pb.h",
+    ".pb.swift",
+    "_pb2.py",
+    "_pb2.pyi",
+    "_pb2_grpc.py",
+    "_pb.js",
+    "_pb.d.ts",
+    ".g.dart",
+    ".freezed.dart",
+    ".designer.cs",
+    ".g.cs",
+    ".generated.cs",
+    ".generated.ts",
+];
+
+/// Comments near the top of a file that mark it as generated. Besides these,
+/// a comment saying both "generated" (in any case) and "DO NOT EDIT", as in
+/// Go's `// Code generated by stringer; DO NOT EDIT.`, marks it too.
+const GENERATED_MARKERS: &[&str] = &["@generated", "<auto-generated"];
+
+/// Why a recognised source file was left out of the counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkipReason {
+    Binary,
+    Generated,
+    Minified,
+    Vendored,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SkipReason::Binary => "binary",
+            SkipReason::Generated => "generated",
+            SkipReason::Minified => "minified",
+            SkipReason::Vendored => "vendored",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Whether `relative`, a path below the scan root, lies in a vendored
+/// dependency directory.
+pub fn is_vendored(relative: &Path) -> bool {
+    relative.parent().is_some_and(|dir| {
+        dir.components().any(|component| match component {
+            Component::Normal(name) => name
+                .to_str()
+                .is_some_and(|name| VENDORED_DIRECTORIES.contains(&name)),
+            _ => false,
+        })
+    })
+}
+
+/// Files recognisable as lockfiles, generator output or minified bundles by
+/// name alone, so they need not be read.
+pub fn check_name(path: &Path) -> Option<SkipReason> {
+    let name = path.file_name()?.to_str()?;
+    if GENERATED_FILENAMES.contains(&name) || GENERATED_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+        Some(SkipReason::Generated)
+    } else if name.contains(".min.") {
+        Some(SkipReason::Minified)
+    } else {
+        None
+    }
+}
+
+/// Binary content, generated-code markers in comments and minified content.
+pub fn check_content(content: &[u8], language: &Language) -> Option<SkipReason> {
+    if is_binary(content) {
+        Some(SkipReason::Binary)
+    } else if is_generated(content, language) {
+        Some(SkipReason::Generated)
+    } else if is_minified(content) {
+        Some(SkipReason::Minified)
+    } else {
+        None
+    }
+}
+
+pub fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_CHECK_BYTES)].contains(&0)
+}
+
+fn is_generated(content: &[u8], language: &Language) -> bool {
+    let head_length = content
+        .iter()
+        .enumerate()
+        .filter(|&(_, &b)| b == b'\n')
+        .nth(GENERATED_MARKER_LINES - 1)
+        .map_or(content.len(), |(i, _)| i + 1);
+    let head = &content[..head_length];
+    head.split_inclusive(|&b| b == b'\n')
+        .zip(counter::classify(head, language))
+        .filter(|&(_, kind)| kind == LineKind::Comment)
+        .any(|(line, _)| {
+            let line = String::from_utf8_lossy(line);
+            GENERATED_MARKERS.iter().any(|marker| line.contains(marker))
+                || (line.contains("DO NOT EDIT") && line.to_lowercase().contains("generated"))
+        })
+}
+
+fn is_minified(content: &[u8]) -> bool {
+    if content.len() < MINIFIED_MIN_BYTES {
+        return false;
+    }
+    let lines = content.split(|&b| b == b'\n').filter(|line| !line.is_empty()).count();
+    content.len() / lines.max(1) > MINIFIED_AVERAGE_LINE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::get_language;
+
+    fn generated(language: &str, content: &str) -> bool {
+        is_generated(content.as_bytes(), get_language(language).unwrap())
+    }
+
+    #[test]
+    fn generated_markers() {
+        assert!(generated("Go", "// Code generated by stringer; DO NOT EDIT.\n\npackage x\n"));
+        let protoc = "# Generated by the protocol buffer compiler.  DO NOT EDIT!\n";
+        assert!(generated("Python", protoc));
+        assert!(generated("JavaScript", "/**\n * @generated SignedSource<<abc>>\n */\n"));
+        assert!(generated("C#", "// <auto-generated />\nclass X {}\n"));
+    }
+
+    #[test]
+    fn markers_outside_comments() {
+        assert!(!generated("Markdown", "# Site\n\nThe site is generated by Hugo.\n"));
+        assert!(!generated("Rust", "// Config values. Do not edit these without asking ops.\n"));
+        assert!(!generated("Python", "MARKER = \"@generated\"\n"));
+        let late = format!("{}// Code generated by hand; DO NOT EDIT.\n", "x := 1\n".repeat(5));
+        assert!(!generated("Go", &late));
+    }
+}
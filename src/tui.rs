@@ -1,4 +1,6 @@
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::path::Path;
 use std::time::{Duration, Instant};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
@@ -14,7 +16,12 @@ use tui::{
     Frame, Terminal,
 };
 
-use crate::{ContributorInfo, LanguageInfo, Stats};
+use crate::languages::{self, Category};
+use crate::output::{format_date, format_integer};
+use crate::{
+    subdirectories, ContributorInfo, DirectoryInfo, FileInfo, LanguageInfo, Problem, RootInfo,
+    Stats,
+};
 
 // Define color constants for consistent styling
 const TITLE_COLOR: Color = Color::Rgb(183, 65, 14);
@@ -22,6 +29,15 @@ const BORDER_COLOR: Color = Color::Rgb(139, 69, 19);
 const TEXT_COLOR: Color = Color::Rgb(255, 160, 122);
 const ERROR_COLOR: Color = Color::Red;
 
+const TABLE_WIDTHS: [Constraint; 6] = [
+    Constraint::Percentage(30),
+    Constraint::Percentage(15),
+    Constraint::Percentage(15),
+    Constraint::Percentage(15),
+    Constraint::Percentage(13),
+    Constraint::Percentage(12),
+];
+
 pub fn run(stats: Stats) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
@@ -49,30 +65,160 @@ pub fn run(stats: Stats) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Number of files listed in the files table, largest first.
+const FILE_TABLE_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Languages,
+    Contributors,
+    Files,
+    Directories,
+    Roots,
+    Problems,
+}
+
 struct App {
+    category_table: Vec<(Category, LanguageInfo)>,
     language_table: Vec<(String, LanguageInfo)>,
     contributor_table: Vec<(String, ContributorInfo)>,
+    file_table: Vec<FileInfo>,
+    /// Number of recognised files left out of the counts, e.g. "3 vendored".
+    skipped_summary: Vec<String>,
+    /// Errors recorded during the scan; the Problems view is only reachable
+    /// when there are any.
+    problem_table: Vec<Problem>,
+    /// The scanned paths; the Roots view is only reachable when there are
+    /// several.
+    root_table: Vec<RootInfo>,
+    directories: BTreeMap<String, DirectoryInfo>,
+    root_dir: String,
+    /// The directory whose subdirectories are listed in the tree pane.
+    current_dir: String,
+    dir_table: Vec<(String, DirectoryInfo)>,
     lang_state: TableState,
     contrib_state: TableState,
-    focus_on_lang: bool,
+    file_state: TableState,
+    dir_state: TableState,
+    problem_state: TableState,
+    root_state: TableState,
+    focus: Focus,
     view_mode: bool,
 }
 
 impl App {
     fn new(stats: Stats) -> App {
-        let mut language_table: Vec<_> = stats.languages.into_iter().collect();
-        language_table.sort_by(|a, b| b.1.lines.cmp(&a.1.lines));
+        let category_table = stats
+            .categories()
+            .into_iter()
+            .map(|group| (group.category, group.totals))
+            .collect();
 
-        let mut contributor_table: Vec<_> = stats.contributors.into_iter().collect();
-        contributor_table.sort_by(|a, b| b.1.lines.cmp(&a.1.lines));
+        let language_table = stats
+            .sorted_languages()
+            .into_iter()
+            .map(|(name, info)| (name.clone(), info.clone()))
+            .collect();
 
-        App {
+        let contributor_table = stats
+            .sorted_contributors()
+            .into_iter()
+            .map(|(name, info)| (name.clone(), info.clone()))
+            .collect();
+
+        let file_table = stats
+            .sorted_files()
+            .into_iter()
+            .take(FILE_TABLE_LIMIT)
+            .cloned()
+            .collect();
+
+        let skipped_summary = stats
+            .skipped_counts()
+            .iter()
+            .map(|(reason, files)| format!("{} {}", format_integer(*files), reason))
+            .collect();
+
+        let problem_table = stats.report.sorted_problems().into_iter().cloned().collect();
+
+        let root_dir = stats.meta.root_key.clone();
+        let mut app = App {
+            category_table,
             language_table,
             contributor_table,
+            file_table,
+            skipped_summary,
+            problem_table,
+            root_table: stats.roots,
+            directories: stats.directories,
+            current_dir: root_dir.clone(),
+            root_dir,
+            dir_table: Vec::new(),
             lang_state: TableState::default(),
             contrib_state: TableState::default(),
-            focus_on_lang: true,
+            file_state: TableState::default(),
+            dir_state: TableState::default(),
+            problem_state: TableState::default(),
+            root_state: TableState::default(),
+            focus: Focus::Languages,
             view_mode: false,
+        };
+        app.load_dir_table();
+        app
+    }
+
+    fn load_dir_table(&mut self) {
+        self.dir_table = subdirectories(&self.directories, &self.current_dir)
+            .into_iter()
+            .map(|(path, info)| (path.clone(), info.clone()))
+            .collect();
+        self.dir_state = TableState::default();
+    }
+
+    /// The directory broken down in the tree pane: the selected
+    /// subdirectory, or the current directory when none is selected.
+    fn selected_directory(&self) -> Option<(&str, &DirectoryInfo)> {
+        match self.dir_state.selected().and_then(|i| self.dir_table.get(i)) {
+            Some((path, info)) => Some((path.as_str(), info)),
+            None => self
+                .directories
+                .get(&self.current_dir)
+                .map(|info| (self.current_dir.as_str(), info)),
+        }
+    }
+
+    fn enter_directory(&mut self) {
+        if self.view_mode || self.focus != Focus::Directories {
+            return;
+        }
+        if let Some((path, _)) = self.dir_state.selected().and_then(|i| self.dir_table.get(i)) {
+            self.current_dir = path.clone();
+            self.load_dir_table();
+        }
+    }
+
+    fn leave_directory(&mut self) {
+        if self.view_mode || self.focus != Focus::Directories || self.current_dir == self.root_dir {
+            return;
+        }
+        let previous = std::mem::take(&mut self.current_dir);
+        self.current_dir = Path::new(&previous)
+            .parent()
+            .map_or_else(|| self.root_dir.clone(), |p| p.display().to_string());
+        self.load_dir_table();
+        let index = self.dir_table.iter().position(|(path, _)| *path == previous);
+        self.dir_state.select(index);
+    }
+
+    /// The state and row count of the table that has focus.
+    fn focused_table(&mut self) -> (&mut TableState, usize) {
+        match self.focus {
+            Focus::Languages => (&mut self.lang_state, self.language_table.len()),
+            Focus::Contributors => (&mut self.contrib_state, self.contributor_table.len()),
+            Focus::Files => (&mut self.file_state, self.file_table.len()),
+            Focus::Directories => (&mut self.dir_state, self.dir_table.len()),
+            Focus::Roots => (&mut self.root_state, self.root_table.len()),
+            Focus::Problems => (&mut self.problem_state, self.problem_table.len()),
         }
     }
 
@@ -80,69 +226,56 @@ impl App {
         if self.view_mode {
             return;
         }
-        if self.focus_on_lang {
-            let i = match self.lang_state.selected() {
-                Some(i) => {
-                    if i >= self.language_table.len() - 1 {
-                        0
-                    } else {
-                        i + 1
-                    }
-                }
-                None => 0,
-            };
-            self.lang_state.select(Some(i));
-        } else {
-            let i = match self.contrib_state.selected() {
-                Some(i) => {
-                    if i >= self.contributor_table.len() - 1 {
-                        0
-                    } else {
-                        i + 1
-                    }
-                }
-                None => 0,
-            };
-            self.contrib_state.select(Some(i));
+        let (state, len) = self.focused_table();
+        if len == 0 {
+            return;
         }
+        let i = match state.selected() {
+            Some(i) => {
+                if i >= len - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        state.select(Some(i));
     }
 
     fn previous(&mut self) {
         if self.view_mode {
             return;
         }
-        if self.focus_on_lang {
-            let i = match self.lang_state.selected() {
-                Some(i) => {
-                    if i == 0 {
-                        self.language_table.len() - 1
-                    } else {
-                        i - 1
-                    }
-                }
-                None => self.language_table.len() - 1,
-            };
-            self.lang_state.select(Some(i));
-        } else {
-            let i = match self.contrib_state.selected() {
-                Some(i) => {
-                    if i == 0 {
-                        self.contributor_table.len() - 1
-                    } else {
-                        i - 1
-                    }
-                }
-                None => self.contributor_table.len() - 1,
-            };
-            self.contrib_state.select(Some(i));
+        let (state, len) = self.focused_table();
+        if len == 0 {
+            return;
         }
+        let i = match state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    len - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => len - 1,
+        };
+        state.select(Some(i));
     }
 
     fn switch_focus(&mut self) {
         if self.view_mode {
             return;
         }
-        self.focus_on_lang = !self.focus_on_lang;
+        self.focus = match self.focus {
+            Focus::Languages => Focus::Contributors,
+            Focus::Contributors => Focus::Files,
+            Focus::Files => Focus::Directories,
+            Focus::Directories if self.root_table.len() > 1 => Focus::Roots,
+            Focus::Directories | Focus::Roots if !self.problem_table.is_empty() => Focus::Problems,
+            Focus::Directories | Focus::Roots | Focus::Problems => Focus::Languages,
+        };
     }
 
     fn toggle_view_mode(&mut self) {
@@ -190,6 +323,12 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result<(), B
                     KeyCode::Char('v') => {
                         app.toggle_view_mode();
                     }
+                    KeyCode::Enter => {
+                        app.enter_directory();
+                    }
+                    KeyCode::Backspace => {
+                        app.leave_directory();
+                    }
                     _ => {}
                 },
                 Event::Resize(_, _) => {
@@ -224,14 +363,26 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             )
             .split(size);
 
-        render_title(f, chunks[0]);
+        render_title(f, chunks[0], app);
         render_tables(f, chunks[1], app);
-        render_help(f, chunks[2]);
+        render_help(f, chunks[2], app);
     }
 }
 
-fn render_title<B: Backend>(f: &mut Frame<B>, area: Rect) {
-    let title = Paragraph::new("RustyLines - Where Every Line Counts")
+fn render_title<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    let mut spans = vec![Span::raw("RustyLines - Where Every Line Counts")];
+    if !app.problem_table.is_empty() {
+        let count = app.problem_table.len();
+        spans.push(Span::styled(
+            format!(
+                "  ({} problem{}, see Problems)",
+                format_integer(count),
+                if count == 1 { "" } else { "s" }
+            ),
+            Style::default().fg(ERROR_COLOR),
+        ));
+    }
+    let title = Paragraph::new(Spans::from(spans))
         .style(Style::default().fg(TITLE_COLOR).add_modifier(Modifier::BOLD))
         .alignment(Alignment::Center)
         .block(
@@ -243,21 +394,76 @@ fn render_title<B: Backend>(f: &mut Frame<B>, area: Rect) {
 }
 
 fn render_tables<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    match app.focus {
+        Focus::Files => return render_file_table(f, area, app),
+        Focus::Directories => return render_directory_tree(f, area, app),
+        Focus::Roots => return render_root_table(f, area, app),
+        Focus::Problems => return render_problem_table(f, area, app),
+        _ => {}
+    }
+
     let tables_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .split(area);
 
-    render_language_table(f, tables_chunks[0], app);
+    // Room for the borders, the header and its margin, and one row per category.
+    let category_height = app.category_table.len() as u16 + 4;
+    let language_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(category_height), Constraint::Min(0)].as_ref())
+        .split(tables_chunks[0]);
+
+    render_category_table(f, language_chunks[0], app);
+    render_language_table(f, language_chunks[1], app);
     render_contributor_table(f, tables_chunks[1], app);
 }
 
+/// Subtotals per language category, shown above the language table.
+fn render_category_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    let header_style = Style::default()
+        .fg(TITLE_COLOR)
+        .add_modifier(Modifier::BOLD);
+
+    let header_cells = ["Categories", "Lines", "Code", "Comments", "Blanks", "Files"]
+        .iter()
+        .map(|h| Cell::from(*h).style(header_style));
+
+    let table_header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = app.category_table.iter().map(|(category, info)| {
+        Row::new(vec![
+            Cell::from(category.to_string()),
+            Cell::from(format_integer(info.lines)),
+            Cell::from(format_integer(info.code)),
+            Cell::from(format_integer(info.comments)),
+            Cell::from(format_integer(info.blanks)),
+            Cell::from(info.files.to_string()),
+        ])
+        .height(1)
+    });
+
+    let table_block = Block::default()
+        .title("Categories")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(BORDER_COLOR));
+
+    let table = Table::new(rows)
+        .header(table_header)
+        .block(table_block)
+        .widths(&TABLE_WIDTHS)
+        .column_spacing(1)
+        .style(Style::default().fg(TEXT_COLOR));
+
+    f.render_widget(table, area);
+}
+
 fn render_language_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
     let header_style = Style::default()
         .fg(TITLE_COLOR)
         .add_modifier(Modifier::BOLD);
 
-    let header_cells = ["Languages", "Lines", "Files"]
+    let header_cells = ["Languages", "Lines", "Code", "Comments", "Blanks", "Files"]
         .iter()
         .map(|h| Cell::from(*h).style(header_style));
 
@@ -267,11 +473,14 @@ fn render_language_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App
         let cells = vec![
             Cell::from(name.clone()),
             Cell::from(format_integer(info.lines)),
+            Cell::from(format_integer(info.code)),
+            Cell::from(format_integer(info.comments)),
+            Cell::from(format_integer(info.blanks)),
             Cell::from(info.files.to_string()),
         ];
         let mut row = Row::new(cells).height(1);
 
-        if app.focus_on_lang && Some(i) == app.lang_state.selected() && !app.view_mode {
+        if app.focus == Focus::Languages && Some(i) == app.lang_state.selected() && !app.view_mode {
             row = row.style(
                 Style::default()
                     .bg(Color::Rgb(205, 92, 92))
@@ -289,11 +498,7 @@ fn render_language_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App
     let table = Table::new(rows)
         .header(table_header)
         .block(table_block)
-        .widths(&[
-            Constraint::Percentage(50),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-        ])
+        .widths(&TABLE_WIDTHS)
         .column_spacing(1)
         .style(Style::default().fg(TEXT_COLOR))
         .highlight_symbol(if app.view_mode { "" } else { ">> " });
@@ -306,7 +511,7 @@ fn render_contributor_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut
         .fg(TITLE_COLOR)
         .add_modifier(Modifier::BOLD);
 
-    let header_cells = ["Developers", "Lines", "Files"]
+    let header_cells = ["Developers", "Lines", "Code", "Comments", "Blanks", "Files"]
         .iter()
         .map(|h| Cell::from(*h).style(header_style));
 
@@ -320,11 +525,17 @@ fn render_contributor_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut
             let cells = vec![
                 Cell::from(name.clone()),
                 Cell::from(format_integer(info.lines)),
+                Cell::from(format_integer(info.code)),
+                Cell::from(format_integer(info.comments)),
+                Cell::from(format_integer(info.blanks)),
                 Cell::from(info.files.to_string()),
             ];
             let mut row = Row::new(cells).height(1);
 
-            if !app.focus_on_lang && Some(i) == app.contrib_state.selected() && !app.view_mode {
+            if app.focus == Focus::Contributors
+                && Some(i) == app.contrib_state.selected()
+                && !app.view_mode
+            {
                 row = row.style(
                     Style::default()
                         .bg(Color::Rgb(205, 92, 92))
@@ -339,23 +550,363 @@ fn render_contributor_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut
         .borders(Borders::ALL)
         .border_style(Style::default().fg(BORDER_COLOR));
 
+    let table = Table::new(rows)
+        .header(table_header)
+        .block(table_block)
+        .widths(&TABLE_WIDTHS)
+        .column_spacing(1)
+        .style(Style::default().fg(TEXT_COLOR))
+        .highlight_symbol(if app.view_mode { "" } else { ">> " });
+
+    f.render_stateful_widget(table, area, &mut app.contrib_state);
+}
+
+fn render_file_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let header_style = Style::default()
+        .fg(TITLE_COLOR)
+        .add_modifier(Modifier::BOLD);
+
+    let header_cells = ["Largest Files", "Language", "Lines", "Code", "Size", "Owner"]
+        .iter()
+        .map(|h| Cell::from(*h).style(header_style));
+
+    let table_header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = app.file_table.iter().enumerate().map(|(i, file)| {
+        let cells = vec![
+            Cell::from(file.path.clone()),
+            Cell::from(file.language.clone()),
+            Cell::from(format_integer(file.lines)),
+            Cell::from(format_integer(file.code)),
+            Cell::from(format_bytes(file.bytes)),
+            Cell::from(file.top_contributor().unwrap_or_default().to_string()),
+        ];
+        let mut row = Row::new(cells).height(1);
+
+        if app.focus == Focus::Files && Some(i) == app.file_state.selected() && !app.view_mode {
+            row = row.style(
+                Style::default()
+                    .bg(Color::Rgb(205, 92, 92))
+                    .add_modifier(Modifier::BOLD),
+            );
+        }
+        row
+    });
+
+    let title = if app.skipped_summary.is_empty() {
+        "Files".to_string()
+    } else {
+        format!("Files (not counted: {})", app.skipped_summary.join(", "))
+    };
+    let table_block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(BORDER_COLOR));
+
     let table = Table::new(rows)
         .header(table_header)
         .block(table_block)
         .widths(&[
-            Constraint::Percentage(50),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
+            Constraint::Percentage(40),
+            Constraint::Percentage(14),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(16),
         ])
         .column_spacing(1)
         .style(Style::default().fg(TEXT_COLOR))
         .highlight_symbol(if app.view_mode { "" } else { ">> " });
 
-    f.render_stateful_widget(table, area, &mut app.contrib_state);
+    f.render_stateful_widget(table, area, &mut app.file_state);
 }
 
-fn render_help<B: Backend>(f: &mut Frame<B>, area: Rect) {
-    let help_text = Spans::from(vec![
+fn render_directory_tree<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    let header_style = Style::default()
+        .fg(TITLE_COLOR)
+        .add_modifier(Modifier::BOLD);
+
+    let header_cells = ["Directories", "Lines", "Files"]
+        .iter()
+        .map(|h| Cell::from(*h).style(header_style));
+
+    let table_header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = app.dir_table.iter().enumerate().map(|(i, (path, info))| {
+        let name = Path::new(path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+        let cells = vec![
+            Cell::from(format!("{}/", name)),
+            Cell::from(format_integer(info.lines)),
+            Cell::from(format_integer(info.files)),
+        ];
+        let mut row = Row::new(cells).height(1);
+
+        if Some(i) == app.dir_state.selected() && !app.view_mode {
+            row = row.style(
+                Style::default()
+                    .bg(Color::Rgb(205, 92, 92))
+                    .add_modifier(Modifier::BOLD),
+            );
+        }
+        row
+    });
+
+    let current = if app.current_dir.is_empty() {
+        "."
+    } else {
+        app.current_dir.as_str()
+    };
+    let table_block = Block::default()
+        .title(format!("Tree: {}", current))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(BORDER_COLOR));
+
+    let table = Table::new(rows)
+        .header(table_header)
+        .block(table_block)
+        .widths(&[
+            Constraint::Percentage(60),
+            Constraint::Percentage(22),
+            Constraint::Percentage(18),
+        ])
+        .column_spacing(1)
+        .style(Style::default().fg(TEXT_COLOR))
+        .highlight_symbol(if app.view_mode { "" } else { ">> " });
+
+    f.render_stateful_widget(table, chunks[0], &mut app.dir_state);
+
+    let breakdown_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(chunks[1]);
+
+    let (languages, contributors) = match app.selected_directory() {
+        Some((_, info)) => {
+            let mut languages: Vec<_> = info
+                .languages
+                .iter()
+                .map(|(name, info)| (name.clone(), info.lines, info.files))
+                .collect();
+            languages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let mut contributors: Vec<_> = info
+                .contributors
+                .iter()
+                .map(|(name, info)| (name.clone(), info.lines, info.files))
+                .collect();
+            contributors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            (languages, contributors)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
+    render_breakdown(f, breakdown_chunks[0], "Languages", &languages);
+    render_breakdown(f, breakdown_chunks[1], "Developers", &contributors);
+}
+
+/// Totals for each path given on the command line.
+fn render_root_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let header_style = Style::default()
+        .fg(TITLE_COLOR)
+        .add_modifier(Modifier::BOLD);
+
+    let header_cells = ["Roots", "Lines", "Code", "Comments", "Blanks", "Files"]
+        .iter()
+        .map(|h| Cell::from(*h).style(header_style));
+
+    let table_header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = app.root_table.iter().enumerate().map(|(i, root)| {
+        let cells = vec![
+            Cell::from(root.path.clone()),
+            Cell::from(format_integer(root.totals.lines)),
+            Cell::from(format_integer(root.totals.code)),
+            Cell::from(format_integer(root.totals.comments)),
+            Cell::from(format_integer(root.totals.blanks)),
+            Cell::from(root.totals.files.to_string()),
+        ];
+        let mut row = Row::new(cells).height(1);
+
+        if Some(i) == app.root_state.selected() && !app.view_mode {
+            row = row.style(
+                Style::default()
+                    .bg(Color::Rgb(205, 92, 92))
+                    .add_modifier(Modifier::BOLD),
+            );
+        }
+        row
+    });
+
+    let table_block = Block::default()
+        .title("Roots")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(BORDER_COLOR));
+
+    let table = Table::new(rows)
+        .header(table_header)
+        .block(table_block)
+        .widths(&TABLE_WIDTHS)
+        .column_spacing(1)
+        .style(Style::default().fg(TEXT_COLOR))
+        .highlight_symbol(if app.view_mode { "" } else { ">> " });
+
+    f.render_stateful_widget(table, area, &mut app.root_state);
+}
+
+/// Files and directories that could not be scanned, and git errors.
+fn render_problem_table<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let header_style = Style::default()
+        .fg(TITLE_COLOR)
+        .add_modifier(Modifier::BOLD);
+
+    let header_cells = ["Path", "Kind", "Message"]
+        .iter()
+        .map(|h| Cell::from(*h).style(header_style));
+
+    let table_header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = app.problem_table.iter().enumerate().map(|(i, problem)| {
+        let cells = vec![
+            Cell::from(problem.path.clone()),
+            Cell::from(problem.kind.to_string()),
+            Cell::from(problem.message.clone()),
+        ];
+        let mut row = Row::new(cells).height(1);
+
+        if Some(i) == app.problem_state.selected() && !app.view_mode {
+            row = row.style(
+                Style::default()
+                    .bg(Color::Rgb(205, 92, 92))
+                    .add_modifier(Modifier::BOLD),
+            );
+        }
+        row
+    });
+
+    let table_block = Block::default()
+        .title("Problems")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(BORDER_COLOR));
+
+    let table = Table::new(rows)
+        .header(table_header)
+        .block(table_block)
+        .widths(&[
+            Constraint::Percentage(35),
+            Constraint::Percentage(20),
+            Constraint::Percentage(45),
+        ])
+        .column_spacing(1)
+        .style(Style::default().fg(TEXT_COLOR))
+        .highlight_symbol(if app.view_mode { "" } else { ">> " });
+
+    f.render_stateful_widget(table, area, &mut app.problem_state);
+}
+
+fn render_breakdown<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    title: &str,
+    rows: &[(String, usize, usize)],
+) {
+    let header_style = Style::default()
+        .fg(TITLE_COLOR)
+        .add_modifier(Modifier::BOLD);
+
+    let header_cells = [title, "Lines", "Files"]
+        .into_iter()
+        .map(|h| Cell::from(h).style(header_style));
+
+    let table_header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = rows.iter().map(|(name, lines, files)| {
+        Row::new(vec![
+            Cell::from(name.clone()),
+            Cell::from(format_integer(*lines)),
+            Cell::from(format_integer(*files)),
+        ])
+        .height(1)
+    });
+
+    let table_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(BORDER_COLOR));
+
+    let table = Table::new(rows)
+        .header(table_header)
+        .block(table_block)
+        .widths(&[
+            Constraint::Percentage(60),
+            Constraint::Percentage(22),
+            Constraint::Percentage(18),
+        ])
+        .column_spacing(1)
+        .style(Style::default().fg(TEXT_COLOR));
+
+    f.render_widget(table, area);
+}
+
+fn render_help<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    let help_text = if app.focus == Focus::Directories {
+        Spans::from(vec![
+            Span::styled(
+                "Enter",
+                Style::default()
+                    .fg(TITLE_COLOR)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" to open, ", Style::default().fg(TEXT_COLOR)),
+            Span::styled(
+                "Backspace",
+                Style::default()
+                    .fg(TITLE_COLOR)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" to go up, ", Style::default().fg(TEXT_COLOR)),
+            Span::styled(
+                "Tab",
+                Style::default()
+                    .fg(TITLE_COLOR)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" to switch tables, ", Style::default().fg(TEXT_COLOR)),
+            Span::styled(
+                "'v'",
+                Style::default()
+                    .fg(TITLE_COLOR)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" to view, ", Style::default().fg(TEXT_COLOR)),
+            Span::styled(
+                "'q'",
+                Style::default()
+                    .fg(TITLE_COLOR)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" to quit", Style::default().fg(TEXT_COLOR)),
+        ])
+    } else {
+        default_help()
+    };
+
+    let help = Paragraph::new(help_text)
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(BORDER_COLOR)),
+        );
+    f.render_widget(help, area);
+}
+
+fn default_help() -> Spans<'static> {
+    Spans::from(vec![
         Span::styled("Use ", Style::default().fg(TEXT_COLOR)),
         Span::styled(
             "Up/Down",
@@ -385,55 +936,151 @@ fn render_help<B: Backend>(f: &mut Frame<B>, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         ),
         Span::styled(" to quit", Style::default().fg(TEXT_COLOR)),
-    ]);
-
-    let help = Paragraph::new(help_text)
-        .alignment(Alignment::Center)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(BORDER_COLOR)),
-        );
-    f.render_widget(help, area);
+    ])
 }
 
 fn render_detailed_view<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
-    let title = if app.focus_on_lang {
-        "Language Details"
-    } else {
-        "Contributor Details"
+    let title = match app.focus {
+        Focus::Languages => "Language Details",
+        Focus::Contributors => "Contributor Details",
+        Focus::Files => "File Details",
+        Focus::Directories => "Directory Details",
+        Focus::Roots => "Root Details",
+        Focus::Problems => "Problem Details",
     };
 
     enum SelectedItem<'a> {
         Language(&'a (String, LanguageInfo)),
         Contributor(&'a (String, ContributorInfo)),
+        File(&'a FileInfo),
+        Directory(&'a str, &'a DirectoryInfo),
+        Root(&'a RootInfo),
+        Problem(&'a Problem),
     }
 
-    let selected_item = if app.focus_on_lang {
-        app.lang_state
+    let selected_item = match app.focus {
+        Focus::Languages => app
+            .lang_state
             .selected()
             .and_then(|i| app.language_table.get(i))
-            .map(SelectedItem::Language)
-    } else {
-        app.contrib_state
+            .map(SelectedItem::Language),
+        Focus::Contributors => app
+            .contrib_state
             .selected()
             .and_then(|i| app.contributor_table.get(i))
-            .map(SelectedItem::Contributor)
+            .map(SelectedItem::Contributor),
+        Focus::Files => app
+            .file_state
+            .selected()
+            .and_then(|i| app.file_table.get(i))
+            .map(SelectedItem::File),
+        Focus::Directories => app
+            .selected_directory()
+            .map(|(path, info)| SelectedItem::Directory(path, info)),
+        Focus::Roots => app
+            .root_state
+            .selected()
+            .and_then(|i| app.root_table.get(i))
+            .map(SelectedItem::Root),
+        Focus::Problems => app
+            .problem_state
+            .selected()
+            .and_then(|i| app.problem_table.get(i))
+            .map(SelectedItem::Problem),
     };
 
     let content = if let Some(item) = selected_item {
         match item {
             SelectedItem::Language((name, info)) => format!(
-                "Language: {}\nTotal Lines: {}\nFiles: {}",
+                "Language: {}\nCategory: {}\nTotal Lines: {}\nCode: {}\nComments: {}\nBlanks: {}\nFiles: {}",
                 name,
+                languages::category_of(name),
                 format_integer(info.lines),
+                format_integer(info.code),
+                format_integer(info.comments),
+                format_integer(info.blanks),
                 info.files
             ),
-            SelectedItem::Contributor((name, info)) => format!(
-                "Contributor: {}\nTotal Lines: {}\nFiles: {}",
-                name,
-                format_integer(info.lines),
-                info.files
+            SelectedItem::Contributor((name, info)) => {
+                let authorship = &info.authorship;
+                let emails: String = authorship
+                    .emails
+                    .iter()
+                    .map(|email| format!("\n  {}", email))
+                    .collect();
+                let date = |time: Option<i64>| time.map_or("-".to_string(), format_date);
+                format!(
+                    "Contributor: {}\nEmails:{}\nTotal Lines: {}\nCode: {}\nComments: {}\nBlanks: {}\nFiles: {}\nCommits: {}\nFirst Commit: {}\nLast Commit: {}",
+                    name,
+                    if emails.is_empty() { " none" } else { &emails },
+                    format_integer(info.lines),
+                    format_integer(info.code),
+                    format_integer(info.comments),
+                    format_integer(info.blanks),
+                    info.files,
+                    format_integer(authorship.commits()),
+                    date(authorship.first_commit),
+                    date(authorship.last_commit)
+                )
+            }
+            SelectedItem::File(file) => {
+                let mut owners: Vec<_> = file.contributors.iter().collect();
+                owners.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                let owners: String = owners
+                    .iter()
+                    .map(|(name, lines)| format!("\n  {}: {}", name, format_integer(**lines)))
+                    .collect();
+                format!(
+                    "File: {}\nLanguage: {} (by {}{})\nTotal Lines: {}\nCode: {}\nComments: {}\nBlanks: {}\nSize: {}\nEncoding: {}\nContributors:{}",
+                    file.path,
+                    file.language,
+                    file.detected_by,
+                    file.heuristic.map(|h| format!(": {}", h)).unwrap_or_default(),
+                    format_integer(file.lines),
+                    format_integer(file.code),
+                    format_integer(file.comments),
+                    format_integer(file.blanks),
+                    format_bytes(file.bytes),
+                    file.encoding,
+                    owners
+                )
+            }
+            SelectedItem::Directory(path, info) => {
+                let languages: String = info
+                    .languages
+                    .iter()
+                    .map(|(name, info)| format!("\n  {}: {}", name, format_integer(info.lines)))
+                    .collect();
+                let contributors: String = info
+                    .contributors
+                    .iter()
+                    .map(|(name, info)| format!("\n  {}: {}", name, format_integer(info.lines)))
+                    .collect();
+                format!(
+                    "Directory: {}\nTotal Lines: {}\nCode: {}\nComments: {}\nBlanks: {}\nFiles: {}\nLanguages:{}\nContributors:{}",
+                    if path.is_empty() { "." } else { path },
+                    format_integer(info.lines),
+                    format_integer(info.code),
+                    format_integer(info.comments),
+                    format_integer(info.blanks),
+                    info.files,
+                    languages,
+                    contributors
+                )
+            }
+            SelectedItem::Root(root) => format!(
+                "Root: {}\nCommit: {}\nTotal Lines: {}\nCode: {}\nComments: {}\nBlanks: {}\nFiles: {}",
+                root.path,
+                root.commit.as_deref().unwrap_or("none"),
+                format_integer(root.totals.lines),
+                format_integer(root.totals.code),
+                format_integer(root.totals.comments),
+                format_integer(root.totals.blanks),
+                root.totals.files
+            ),
+            SelectedItem::Problem(problem) => format!(
+                "Path: {}\nKind: {}\nMessage: {}",
+                problem.path, problem.kind, problem.message
             ),
         }
     } else {
@@ -453,17 +1100,17 @@ fn render_detailed_view<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
     f.render_widget(paragraph, area);
 }
 
-fn format_integer(n: usize) -> String {
-    let s = n.to_string();
-    let bytes = s.as_bytes();
-    let len = bytes.len();
-    let mut result = String::new();
-
-    for (i, &b) in bytes.iter().enumerate() {
-        result.push(b as char);
-        if (len - i - 1) % 3 == 0 && i != len - 1 {
-            result.push(',');
-        }
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
-    result
 }